@@ -0,0 +1,54 @@
+//! The allocator counting the memory used by the benchmarks, and the measurement shared by all of them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use r1thm::{Bn254, R1CS};
+
+/// An allocator keeping track of the current and the peak number of allocated bytes.
+pub struct CountingAllocator;
+
+pub static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+pub static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+pub static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Converts a number of bytes to MiB.
+pub fn megabytes(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Runs `compile`, which returns the number of constraints and the system, and prints its
+/// time and memory use. The memory is counted from the start of `compile`.
+pub fn measure(label: &str, compile: impl FnOnce() -> (usize, R1CS<Bn254>)) {
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let start = Instant::now();
+    let (size, r1cs) = compile();
+    let elapsed = start.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - baseline;
+    let retained = ALLOCATED.load(Ordering::Relaxed) - baseline;
+    println!(
+        "{:<26}: {:>8} constraints in {:>8.2?}, peak {:>8.1} MiB, system {:>8.1} MiB",
+        label, size, elapsed, megabytes(peak), megabytes(retained)
+    );
+    drop(r1cs);
+}
@@ -0,0 +1,44 @@
+//! Measures the compile time and memory use of `poly2r1cs` on large generated polynomials.
+//!
+//! Run with `cargo bench --bench compile`, optionally followed by the numbers of terms to use,
+//! e.g. `cargo bench --bench compile -- 100000 1000000`. For each polynomial, the benchmark
+//! reports the time taken, the peak memory allocated while compiling, and the memory retained
+//! by the resulting system. The same is reported for the streaming front-end, reading the
+//! polynomial from memory and passing the constraints to a sink which only counts them.
+
+mod common;
+
+use common::measure;
+
+use r1thm::{poly2r1cs, poly2r1cs_streaming, Bn254, R1CS};
+
+/// Generates a polynomial with `n` terms of the form `c x_i^e y_j`, over roughly `sqrt(n)` variables.
+fn generate_polynomial(n: usize) -> String {
+    let variables = (n as f64).sqrt() as usize + 1;
+    let terms: Vec<String> = (0..n)
+        .map(|i| format!("{}x_{}^{} y_{}", i % 97 + 2, i % variables, i % 5 + 1, (i / variables) % variables))
+        .collect();
+    terms.join(" + ")
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1)
+        .filter_map(|argument| argument.parse().ok())
+        .collect();
+    let sizes = if sizes.is_empty() { vec![10_000, 100_000] } else { sizes };
+    for n in sizes {
+        let polynomial = generate_polynomial(n);
+        measure(&format!("{:>8} terms", n), || {
+            let r1cs = poly2r1cs(polynomial.clone(), 0).expect("The generated polynomial does not compile.");
+            (r1cs.size(), r1cs)
+        });
+        measure(&format!("{:>8} terms (streaming)", n), || {
+            let mut size = 0;
+            let r1cs: R1CS<Bn254> = poly2r1cs_streaming(polynomial.as_bytes(), 0, |_| {
+                size += 1;
+                Ok(())
+            }).expect("The generated polynomial does not compile.");
+            (size, r1cs)
+        });
+    }
+}
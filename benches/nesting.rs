@@ -0,0 +1,46 @@
+//! Measures the compile time and memory use of `poly2r1cs` on deeply nested polynomials.
+//!
+//! Run with `cargo bench --bench nesting`, optionally followed by the nesting depths to use,
+//! e.g. `cargo bench --bench nesting -- 1000 2000 4000`. The polynomials are written in Horner
+//! form, `(((x + 1) x + 2) x + 3) ...`, so that the text of the parenthesis at depth `d` has a
+//! length proportional to `d`. The peak memory should grow linearly with the depth.
+//! The benchmark runs in a thread with a large stack, since parsing recurses once per level.
+
+mod common;
+
+use common::measure;
+
+use r1thm::poly2r1cs;
+
+/// Generates the polynomial `(...((x + 1) x + 2) x + ... ) x + n` with `n` nested parentheses.
+fn generate_polynomial(n: usize) -> String {
+    let mut polynomial = String::from("x");
+    for i in 1..=n {
+        polynomial = format!("({} + {}) x", polynomial, i);
+    }
+    polynomial
+}
+
+fn main() {
+    // Parsing and visiting recurse once per level of nesting, which needs more than the default stack.
+    std::thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn run() {
+    let depths: Vec<usize> = std::env::args().skip(1)
+        .filter_map(|argument| argument.parse().ok())
+        .collect();
+    let depths = if depths.is_empty() { vec![500, 1000, 2000] } else { depths };
+    for n in depths {
+        let polynomial = generate_polynomial(n);
+        measure(&format!("depth {:>6}", n), || {
+            let r1cs = poly2r1cs(polynomial.clone(), 0).expect("The generated polynomial does not compile.");
+            (r1cs.size(), r1cs)
+        });
+    }
+}
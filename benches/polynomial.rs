@@ -0,0 +1,58 @@
+//! Compares the multiplication of polynomials by multiplying each pair of coefficients with the
+//! multiplication using the NTT, over each of the fields of the library.
+//!
+//! Run with `cargo bench --bench polynomial`, optionally followed by the numbers of coefficients of
+//! the polynomials to multiply, e.g. `cargo bench --bench polynomial -- 1024 4096`. The naive
+//! multiplication takes time quadratic in the number of coefficients, and the NTT time `O(n log n)`,
+//! so the NTT should be faster from some size on (see `NTT_THRESHOLD` in *src/polynomial.rs*).
+
+use std::time::{Duration, Instant};
+
+use r1thm::{BabyBear, Bls12_381, Bn254, Field, Goldilocks, UnivariatePolynomial};
+
+/// Generates a polynomial with `n` pseudo-random coefficients, given by a linear congruential generator.
+fn generate_polynomial<F: Field>(n: usize, seed: u64) -> UnivariatePolynomial<F> {
+    let coefficients = std::iter::successors(Some(seed), |x| Some(x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407)))
+        .skip(1)
+        .take(n)
+        .map(F::from)
+        .collect();
+    UnivariatePolynomial::new(coefficients)
+}
+
+/// Returns the average time of `f`, running it repeatedly for at least 100 ms.
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < Duration::from_millis(100) {
+        std::hint::black_box(f());
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+/// Multiplies two polynomials with `n` coefficients both ways, and prints the times.
+fn compare<F: Field>(field: &str, n: usize) {
+    let p = generate_polynomial::<F>(n, 1);
+    let q = generate_polynomial::<F>(n, 2);
+    assert_eq!(p.mul_naive(&q), p.mul_ntt(&q).unwrap(), "The products differ.");
+    let naive = time(|| p.mul_naive(&q));
+    let ntt = time(|| p.mul_ntt(&q).unwrap());
+    println!(
+        "{:<10} {:>7} coefficients: naive {:>10.2?}, NTT {:>10.2?} ({:.1}x)",
+        field, n, naive, ntt, naive.as_secs_f64() / ntt.as_secs_f64()
+    );
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args().skip(1)
+        .filter_map(|argument| argument.parse().ok())
+        .collect();
+    let sizes = if sizes.is_empty() { vec![16, 32, 64, 128, 256, 1024, 4096] } else { sizes };
+    for n in sizes {
+        compare::<Bn254>("BN254", n);
+        compare::<Bls12_381>("BLS12-381", n);
+        compare::<Goldilocks>("Goldilocks", n);
+        compare::<BabyBear>("BabyBear", n);
+    }
+}
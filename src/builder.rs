@@ -0,0 +1,328 @@
+//! This module implements a builder for R1CS systems, as an alternative to parsing polynomials.
+//!
+//! A `ConstraintSystem` hands out `Variable`s, which can be combined using the usual arithmetic
+//! operators. Each operation appends the constraints needed to compute its result, so that e.g.
+//!
+//! ```
+//! use r1thm::{Bn254, ConstraintSystem};
+//!
+//! let cs = ConstraintSystem::<Bn254>::new();
+//! let x = cs.input("x");
+//! let y = cs.input("y");
+//! let z = (x.clone() * y + 3) * x;
+//! cs.assert_equal(z, Bn254::from(10i64));
+//! let r1cs = cs.into_r1cs();
+//! ```
+//!
+//! gives the same system as `poly2r1cs("(x*y + 3)*x", 10)`. The lowering of the operations to
+//! constraints is shared with the parser (see the module `lowering`).
+//!
+//! The parser gives identical subexpressions a single variable, and so does the builder: equal
+//! constants, linear combinations, products, powers and multiples share the variable created for
+//! the first of them. Hence `(x.clone() + 1) * (x + 1)` gives the same system as `(x + 1)*(x + 1)`.
+//! Since the builder compares values rather than text, it may share more than the parser, e.g.
+//! `x + y` and `y + x`, which the parser treats as different subexpressions.
+//!
+//! Just as for a parsed expression, sums and differences are not turned into constraints right
+//! away. A `Variable` is a linear combination `Σ x_i u_i` of variables in the system, and a single
+//! constraint for the whole sum is created when it is needed, i.e. when it is multiplied or raised
+//! to a power, or when an equation is asserted.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::rc::Rc;
+
+use crate::field::{Bn254, Field};
+use crate::linear_combination::LinearCombination;
+use crate::lowering;
+use crate::r1cs::R1CS;
+
+/// A R1CS system under construction. Constraints are added by combining the `Variable`s
+/// returned by `input` and `constant`.
+pub struct ConstraintSystem<F: Field = Bn254> {
+    r1cs: RefCell<R1CS<F>>,
+    /// The variables created for operations, so that equal operations share their variable.
+    variables: RefCell<HashMap<Operation<F>, usize>>,
+}
+
+/// An operation whose result gets a variable of its own.
+#[derive(PartialEq, Eq, Hash)]
+enum Operation<F: Field> {
+    /// The constant `s`.
+    Constant(F),
+    /// The linear combination `Σ x_i u_i`.
+    LinearCombination(LinearCombination<F>),
+    /// The product `u_i * u_j`.
+    Product(usize, usize),
+    /// The power `u_i^e`.
+    Power(usize, u32),
+    /// The multiple `s * u_j`.
+    Multiple(F, usize),
+}
+
+/// A value in a `ConstraintSystem`, given as a linear combination `Σ x_i u_i` of the variables
+/// of the system.
+///
+/// Clones of a `Variable` share the variable created for the linear combination, so that it
+/// is only constrained once, however many times it is used.
+///
+/// # Panics
+/// Combining variables of different constraint systems using `+`, `-` or `*` panics.
+#[derive(Clone)]
+pub struct Variable<'cs, F: Field = Bn254> {
+    cs: &'cs ConstraintSystem<F>,
+    terms: LinearCombination<F>,
+    index: Rc<Cell<Option<usize>>>,
+}
+
+impl<F: Field> Default for ConstraintSystem<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> ConstraintSystem<F> {
+    /// Creates a new, empty constraint system.
+    pub fn new() -> Self {
+        ConstraintSystem {
+            r1cs: RefCell::new(R1CS::new()),
+            variables: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the input variable with the given name, adding it to the system if it is new.
+    pub fn input(&self, name: &str) -> Variable<'_, F> {
+        let mut r1cs = self.r1cs.borrow_mut();
+        let index = match r1cs.variables().get(name) {
+            Some(index) => *index,
+            None => r1cs.add_input_variable(name.to_string()),
+        };
+        Variable::from_index(self, index)
+    }
+
+    /// Returns the variable for the constant `s`, with the constraint `1 * s = u_k`. The variable
+    /// is only created for the first use of `s`.
+    pub fn constant(&self, s: F) -> Variable<'_, F> {
+        let index = self.shared(Operation::Constant(s), |r1cs| lowering::constant(r1cs, s));
+        Variable::from_index(self, index)
+    }
+
+    /// Adds the constraint `v = s`.
+    ///
+    /// # Panics
+    /// Panics if `v` belongs to another constraint system.
+    pub fn assert_equal(&self, v: Variable<'_, F>, s: F) {
+        self.assert_equation(v, s, false);
+    }
+
+    /// Adds constraints stating that `v != s`, using an auxiliary variable for the inverse of `v - s`.
+    ///
+    /// # Panics
+    /// Panics if `v` belongs to another constraint system.
+    pub fn assert_not_equal(&self, v: Variable<'_, F>, s: F) {
+        self.assert_equation(v, s, true);
+    }
+
+    fn assert_equation(&self, v: Variable<'_, F>, s: F, is_inequality: bool) {
+        assert!(std::ptr::eq(self, v.cs), "The variable belongs to another constraint system.");
+        let i = v.index();
+        lowering::assert_equation(&mut self.r1cs.borrow_mut(), i, s, is_inequality);
+    }
+
+    /// Returns the variable of an operation, which is created using `create` unless an equal
+    /// operation already has a variable.
+    fn shared(&self, operation: Operation<F>, create: impl FnOnce(&mut R1CS<F>) -> usize) -> usize {
+        if let Some(index) = self.variables.borrow().get(&operation) {
+            return *index;
+        }
+        let index = create(&mut self.r1cs.borrow_mut());
+        self.variables.borrow_mut().insert(operation, index);
+        index
+    }
+
+    /// Returns the constructed R1CS system.
+    pub fn into_r1cs(self) -> R1CS<F> {
+        self.r1cs.into_inner()
+    }
+}
+
+impl<'cs, F: Field> Variable<'cs, F> {
+    /// Creates the variable `u_i` of the system.
+    fn from_index(cs: &'cs ConstraintSystem<F>, i: usize) -> Self {
+        Variable {
+            cs,
+            terms: LinearCombination::variable(i),
+            index: Rc::new(Cell::new(Some(i))),
+        }
+    }
+
+    /// Creates a variable for the linear combination given by `terms`.
+    fn from_terms(cs: &'cs ConstraintSystem<F>, terms: LinearCombination<F>) -> Self {
+        Variable {
+            cs,
+            terms,
+            index: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// Returns the index of the variable in the system. If the variable is a linear combination
+    /// of several variables, this creates a new variable `u_k` with the constraint `Σ x_i u_i = u_k`.
+    pub fn index(&self) -> usize {
+        if let Some(i) = self.index.get() {
+            return i;
+        }
+        let terms = self.terms.clone();
+        let i = self.cs.shared(Operation::LinearCombination(self.terms.clone()), |r1cs| lowering::linear_combination(r1cs, terms));
+        self.index.set(Some(i));
+        i
+    }
+
+    /// Raises the variable to the given power, using binary exponentiation.
+    pub fn pow(self, exponent: u32) -> Self {
+        let i = self.index();
+        let k = self.cs.shared(Operation::Power(i, exponent), |r1cs| lowering::power(r1cs, i, exponent));
+        Variable::from_index(self.cs, k)
+    }
+
+    /// Returns the linear combination `self + s * other`, leaving any constraints to be created later.
+    fn add_multiple(self, other: Variable<'cs, F>, s: F) -> Self {
+        assert!(std::ptr::eq(self.cs, other.cs), "Cannot combine variables of different constraint systems.");
+        Variable::from_terms(self.cs, self.terms + other.terms * s)
+    }
+}
+
+impl<'cs, F: Field> Add for Variable<'cs, F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.add_multiple(other, F::one())
+    }
+}
+
+impl<'cs, F: Field> Sub for Variable<'cs, F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.add_multiple(other, -F::one())
+    }
+}
+
+impl<'cs, F: Field> Mul for Variable<'cs, F> {
+    type Output = Self;
+
+    /// Multiplies two variables, using the constraint `u_i * u_j = u_k`.
+    fn mul(self, other: Self) -> Self {
+        assert!(std::ptr::eq(self.cs, other.cs), "Cannot combine variables of different constraint systems.");
+        let (i, j) = (self.index(), other.index());
+        let k = self.cs.shared(Operation::Product(i, j), |r1cs| lowering::product(r1cs, &[i, j]));
+        Variable::from_index(self.cs, k)
+    }
+}
+
+impl<'cs, F: Field> Neg for Variable<'cs, F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Variable::from_terms(self.cs, -self.terms)
+    }
+}
+
+impl<'cs, F: Field> Add<i64> for Variable<'cs, F> {
+    type Output = Self;
+
+    /// Adds a constant, which (as in the parser) gets a variable of its own, shared by its uses.
+    fn add(self, s: i64) -> Self {
+        let constant = self.cs.constant(F::from(s));
+        self + constant
+    }
+}
+
+impl<'cs, F: Field> Sub<i64> for Variable<'cs, F> {
+    type Output = Self;
+
+    /// Subtracts a constant, which (as in the parser) gets a variable of its own, shared by its uses.
+    fn sub(self, s: i64) -> Self {
+        let constant = self.cs.constant(F::from(s));
+        self - constant
+    }
+}
+
+impl<'cs, F: Field> Mul<i64> for Variable<'cs, F> {
+    type Output = Self;
+
+    /// Multiplies by a constant `s`, using the constraint `s * u_j = u_k`.
+    fn mul(self, s: i64) -> Self {
+        let j = self.index();
+        let k = self.cs.shared(Operation::Multiple(F::from(s), j), |r1cs| lowering::scale(r1cs, F::from(s), j));
+        Variable::from_index(self.cs, k)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::parser::{equation2r1cs, poly2r1cs};
+
+    /// Checks that two systems have the same variables and constraints.
+    fn assert_same_system(built: &R1CS, parsed: &R1CS) {
+        assert_eq!(built.num_variables(), parsed.num_variables());
+        assert_eq!(built.inputs().collect::<Vec<_>>(), parsed.inputs().collect::<Vec<_>>());
+        assert!(built.constraints().eq(parsed.constraints()), "The built and parsed systems have different constraints.");
+    }
+
+    #[test]
+    fn test_builder_matches_parser() {
+        let cs = ConstraintSystem::new();
+        let x = cs.input("x");
+        let y = cs.input("y");
+        let z = (x.clone() * y + 3) * x;
+        cs.assert_equal(z, Bn254::from(10i64));
+        assert_same_system(&cs.into_r1cs(), &poly2r1cs("(x*y + 3)*x".to_string(), 10).unwrap());
+    }
+
+    #[test]
+    fn test_builder_matches_parser_with_powers_and_coefficients() {
+        let cs = ConstraintSystem::new();
+        let x = cs.input("x");
+        // The input `y` is created where it is first used, just as when parsing.
+        let z = x.clone().pow(3) - x * 2 + 1 - (cs.input("y") + cs.input("y")).pow(2);
+        cs.assert_not_equal(z, Bn254::from(4i64));
+        assert_same_system(&cs.into_r1cs(), &equation2r1cs("x^3 - 2x + 1 - (y + y)^2 != 4".to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_builder_matches_parser_with_repeated_subexpressions() {
+        let cs = ConstraintSystem::new();
+        let x = cs.input("x");
+        cs.assert_equal((x.clone() + 1) * (x + 1), Bn254::from(4i64));
+        let built = cs.into_r1cs();
+        let parsed = poly2r1cs("(x+1)*(x+1)".to_string(), 4).unwrap();
+        assert_eq!(built.size(), 4);
+        assert_same_system(&built, &parsed);
+
+        let cs = ConstraintSystem::new();
+        let x = cs.input("x");
+        let y = || cs.input("y");
+        let z = x.clone().pow(2) * y() + x.clone().pow(2) * y() - (x.clone() * y() + 2) * (x * y() + 2);
+        cs.assert_equal(z, Bn254::from(1i64));
+        assert_same_system(&cs.into_r1cs(), &poly2r1cs("x^2*y + x^2*y - (x*y + 2)*(x*y + 2)".to_string(), 1).unwrap());
+    }
+
+    #[test]
+    fn test_shared_linear_combination() {
+        let cs = ConstraintSystem::<Bn254>::new();
+        let x = cs.input("x");
+        let s = x.clone() + cs.input("y");
+        let z = s.clone() * s;
+        cs.assert_equal(z, Bn254::from(25i64));
+        let r1cs = cs.into_r1cs();
+        assert_eq!(r1cs.size(), 3);
+        let inputs = HashMap::from([("x".to_string(), Bn254::from(2i64)), ("y".to_string(), Bn254::from(3i64))]);
+        assert!(r1cs.generate_witness(&inputs).is_ok());
+        let inputs = HashMap::from([("x".to_string(), Bn254::from(2i64)), ("y".to_string(), Bn254::from(2i64))]);
+        assert!(r1cs.generate_witness(&inputs).is_err());
+    }
+}
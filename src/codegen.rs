@@ -0,0 +1,539 @@
+//! This module generates witness calculators: standalone Rust source files computing the
+//! witness of a particular R1CS system from its inputs.
+//!
+//! Generating a witness with `R1CS::generate_witness` first has to find out in which order the
+//! variables can be computed, and then walks the constraints of the system. A witness calculator
+//! does this work once, when it is generated: it consists of straight-line code with one statement
+//! per variable, such as `w[7] = (w[1] + C[0]) * w[3];`, followed by checks of the constraints
+//! which were not used to compute a variable. This is similar to the C++ witness calculators
+//! generated by circom.
+//!
+//! The generated file has no dependencies. It contains its own implementation of the field of the
+//! system (with the same Montgomery arithmetic as `Fp`), the constants of the system, and the
+//! function `calculate_witness`. It contains no inner attributes, so that it can be used as a
+//! module, or be included by `include!` from a file written by a build script.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use indexmap::IndexSet;
+
+use crate::field::Field;
+use crate::r1cs::R1CS;
+use crate::witness::{Hint, Step};
+
+/// The field arithmetic of the generated file. `MODULUS_LIMBS` is replaced by the limbs of the modulus.
+const FIELD_SOURCE: &str = r#"/// An element of the prime field of the circuit, stored in Montgomery form as four 64-bit limbs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fp([u64; 4]);
+
+/// The modulus of the field, as little-endian 64-bit limbs.
+const MODULUS: [u64; 4] = MODULUS_LIMBS;
+
+/// `2^256 mod p`, which is the Montgomery form of `1`.
+const R: [u64; 4] = power_of_two(256);
+
+/// `2^512 mod p`, which is used to convert into Montgomery form.
+const R2: [u64; 4] = power_of_two(512);
+
+/// `-p^{-1} mod 2^64`, which is needed for Montgomery reduction.
+const INV: u64 = {
+    let mut inv = 1u64;
+    let mut i = 0;
+    while i < 63 {
+        inv = inv.wrapping_mul(inv);
+        inv = inv.wrapping_mul(MODULUS[0]);
+        i += 1;
+    }
+    inv.wrapping_neg()
+};
+
+/// Adds two 256-bit numbers, returning the sum and the carry.
+const fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut result = [0u64; 4];
+    let mut carry = 0u128;
+    let mut i = 0;
+    while i < 4 {
+        let sum = a[i] as u128 + b[i] as u128 + carry;
+        result[i] = sum as u64;
+        carry = sum >> 64;
+        i += 1;
+    }
+    (result, carry != 0)
+}
+
+/// Subtracts two 256-bit numbers, returning the difference and the borrow.
+const fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut result = [0u64; 4];
+    let mut borrow = false;
+    let mut i = 0;
+    while i < 4 {
+        let (difference, borrow_1) = a[i].overflowing_sub(b[i]);
+        let (difference, borrow_2) = difference.overflowing_sub(borrow as u64);
+        result[i] = difference;
+        borrow = borrow_1 || borrow_2;
+        i += 1;
+    }
+    (result, borrow)
+}
+
+/// Checks whether `a >= b` for two 256-bit numbers.
+const fn greater_or_equal(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    let mut i = 4;
+    while i > 0 {
+        i -= 1;
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    true
+}
+
+/// Computes `a + b mod p`, assuming that `a` and `b` are already reduced.
+const fn add_mod(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let (sum, carry) = add_limbs(a, b);
+    if carry || greater_or_equal(&sum, &MODULUS) {
+        sub_limbs(&sum, &MODULUS).0
+    } else {
+        sum
+    }
+}
+
+/// Computes `2^n mod p` by repeated doubling.
+const fn power_of_two(n: usize) -> [u64; 4] {
+    let mut r = [1u64, 0, 0, 0];
+    let mut i = 0;
+    while i < n {
+        r = add_mod(&r, &r);
+        i += 1;
+    }
+    r
+}
+
+/// Computes `a * b * 2^-256 mod p` using the CIOS method.
+fn montgomery_multiplication(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let p = &MODULUS;
+    let mut t = [0u64; 6];
+    for b_i in b.iter() {
+        let mut carry = 0u128;
+        for j in 0..4 {
+            let product = t[j] as u128 + a[j] as u128 * *b_i as u128 + carry;
+            t[j] = product as u64;
+            carry = product >> 64;
+        }
+        let sum = t[4] as u128 + carry;
+        t[4] = sum as u64;
+        t[5] = (sum >> 64) as u64;
+
+        let m = t[0].wrapping_mul(INV);
+        let mut carry = (t[0] as u128 + m as u128 * p[0] as u128) >> 64;
+        for j in 1..4 {
+            let product = t[j] as u128 + m as u128 * p[j] as u128 + carry;
+            t[j - 1] = product as u64;
+            carry = product >> 64;
+        }
+        let sum = t[4] as u128 + carry;
+        t[3] = sum as u64;
+        t[4] = t[5] + (sum >> 64) as u64;
+    }
+    let result = [t[0], t[1], t[2], t[3]];
+    if t[4] != 0 || greater_or_equal(&result, p) {
+        sub_limbs(&result, p).0
+    } else {
+        result
+    }
+}
+
+#[allow(dead_code)]
+impl Fp {
+    /// The additive identity.
+    pub const ZERO: Fp = Fp([0; 4]);
+
+    /// The multiplicative identity.
+    pub const ONE: Fp = Fp(R);
+
+    /// Creates an element from a number given as little-endian 64-bit limbs, reducing it modulo `p`.
+    pub fn from_limbs(limbs: [u64; 4]) -> Fp {
+        Fp(montgomery_multiplication(&limbs, &R2))
+    }
+
+    /// Creates an element from a small number.
+    pub fn from_u64(value: u64) -> Fp {
+        Fp::from_limbs([value, 0, 0, 0])
+    }
+
+    /// Returns the smallest non-negative integer representing the element, as little-endian 64-bit limbs.
+    pub fn to_limbs(self) -> [u64; 4] {
+        montgomery_multiplication(&self.0, &[1, 0, 0, 0])
+    }
+
+    /// Returns the inverse of the element, computed as `x^(p - 2)`, or `None` if the element is zero.
+    pub fn inverse(self) -> Option<Fp> {
+        if self == Fp::ZERO {
+            return None;
+        }
+        let exponent = sub_limbs(&MODULUS, &[2, 0, 0, 0]).0;
+        let mut result = Fp::ONE;
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result * result;
+                if (limb >> bit) & 1 == 1 {
+                    result = result * self;
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns the number of bits of the smallest non-negative integer representing the element.
+    pub fn bit_length(self) -> usize {
+        let limbs = self.to_limbs();
+        match limbs.iter().rposition(|limb| *limb != 0) {
+            Some(i) => 64 * i + 64 - limbs[i].leading_zeros() as usize,
+            None => 0,
+        }
+    }
+}
+
+impl std::ops::Add for Fp {
+    type Output = Fp;
+
+    fn add(self, other: Fp) -> Fp {
+        Fp(add_mod(&self.0, &other.0))
+    }
+}
+
+impl std::ops::Sub for Fp {
+    type Output = Fp;
+
+    fn sub(self, other: Fp) -> Fp {
+        let (difference, borrow) = sub_limbs(&self.0, &other.0);
+        if borrow {
+            Fp(add_limbs(&difference, &MODULUS).0)
+        } else {
+            Fp(difference)
+        }
+    }
+}
+
+impl std::ops::Mul for Fp {
+    type Output = Fp;
+
+    fn mul(self, other: Fp) -> Fp {
+        Fp(montgomery_multiplication(&self.0, &other.0))
+    }
+}
+
+impl std::ops::Neg for Fp {
+    type Output = Fp;
+
+    fn neg(self) -> Fp {
+        Fp::ZERO - self
+    }
+}
+
+/// Prints the element as the smallest non-negative integer representing it, in base 10.
+impl std::fmt::Display for Fp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut limbs = self.to_limbs();
+        let mut digits = Vec::new();
+        loop {
+            let mut remainder = 0u128;
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 64) | *limb as u128;
+                *limb = (current / 10) as u64;
+                remainder = current % 10;
+            }
+            digits.push(char::from(b'0' + remainder as u8));
+            if limbs == [0; 4] {
+                break;
+            }
+        }
+        let digits: String = digits.iter().rev().collect();
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl std::fmt::Debug for Fp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+/// Returns bit number `index` of the variable `u_source`, whose value is expected to fit in `size` bits.
+#[allow(dead_code)]
+fn bit(value: Fp, index: usize, size: usize, source: usize) -> Result<Fp, String> {
+    if value.bit_length() > size {
+        return Err(format!("Cannot compute the bits of u_{}, since its value {} does not fit in {} bits", source, value, size));
+    }
+    // Bits beyond the limbs, which exist when size exceeds 256, are zero.
+    let set = value.to_limbs().get(index / 64).is_some_and(|limb| (limb >> (index % 64)) & 1 == 1);
+    Ok(if set { Fp::ONE } else { Fp::ZERO })
+}
+"#;
+
+/// Returns the smallest non-negative integer representing an element, as little-endian 64-bit limbs.
+fn limbs<F: Field>(x: F) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (index, limb) in limbs.iter_mut().enumerate() {
+        for bit in 0..64 {
+            if x.bit(64 * index + bit) {
+                *limb |= 1 << bit;
+            }
+        }
+    }
+    limbs
+}
+
+/// Puts an expression in parentheses, unless it is a single constant or variable, so that it can be used as an operand.
+fn operand(expression: String) -> String {
+    if expression.contains(' ') {
+        format!("({})", expression)
+    } else {
+        expression
+    }
+}
+
+/// Formats limbs as a Rust array literal.
+fn limbs_literal(limbs: [u64; 4]) -> String {
+    format!("[0x{:016x}, 0x{:016x}, 0x{:016x}, 0x{:016x}]", limbs[0], limbs[1], limbs[2], limbs[3])
+}
+
+/// The constants of the generated file. Each distinct coefficient (other than `1`) gets an entry
+/// of the table `C`, in the order in which the coefficients are first used.
+struct Constants<F: Field> {
+    values: IndexSet<F>,
+}
+
+impl<F: Field> Constants<F> {
+    /// Returns the expression of the sum `Σ x_i u_i` given by the terms `(i, x_i)`, as Rust code.
+    fn sum(&mut self, terms: &[(usize, F)]) -> String {
+        if terms.is_empty() {
+            return "Fp::ZERO".to_string();
+        }
+        let terms: Vec<String> = terms.iter().map(|(i, x)| match (*i, *x == F::one()) {
+            (0, true) => "Fp::ONE".to_string(),
+            (0, false) => self.constant(*x),
+            (i, true) => format!("w[{}]", i),
+            (i, false) => format!("{} * w[{}]", self.constant(*x), i),
+        }).collect();
+        terms.join(" + ")
+    }
+
+    /// Returns the expression of the sum `Σ x_i u_i`, as Rust code which can be used as an operand,
+    /// i.e. in parentheses unless it is a single constant or variable.
+    fn operand(&mut self, terms: &[(usize, F)]) -> String {
+        operand(self.sum(terms))
+    }
+
+    /// Returns the expression of the constant `x`, as Rust code.
+    fn constant(&mut self, x: F) -> String {
+        let (index, _) = self.values.insert_full(x);
+        format!("C[{}]", index)
+    }
+
+    /// Returns the declaration of the table `C` of constants, as Rust code.
+    fn declaration(&self) -> String {
+        // Multiplying by 2^256 gives the Montgomery form of each constant.
+        let montgomery_factor = F::from(2u64).pow(256);
+        let mut declaration = format!("/// The constants of the circuit, in Montgomery form.\nconst C: [Fp; {}] = [\n", self.values.len());
+        for value in self.values.iter() {
+            let _ = writeln!(declaration, "    Fp({}), // {}", limbs_literal(limbs(*value * montgomery_factor)), value);
+        }
+        declaration.push_str("];\n");
+        declaration
+    }
+}
+
+impl<F: Field> R1CS<F> {
+    /// Generates the source code of a witness calculator for the system: a standalone Rust file,
+    /// without dependencies, which computes the witness of the system with straight-line code.
+    ///
+    /// The file defines the field element type `Fp`, the names `INPUTS` of the inputs, and the function
+    /// `calculate_witness(inputs: &[Fp; NUM_INPUTS]) -> Result<Vec<Fp>, String>`, which takes the values
+    /// of the inputs in the order of `INPUTS`, and returns the same witness (or error) as `generate_witness`.
+    /// An error is returned if some variable cannot be computed from the inputs.
+    pub fn witness_calculator(&self) -> Result<String, String> {
+        let schedule = self.witness_schedule()?;
+        let mut constants = Constants { values: IndexSet::new() };
+        let mut used_constraints = vec![false; self.size()];
+        let mut body = String::new();
+
+        let _ = writeln!(body, "    let mut w = vec![Fp::ZERO; NUM_VARIABLES];");
+        let _ = writeln!(body, "    w[0] = Fp::ONE;");
+        for (k, (name, position)) in self.inputs().enumerate() {
+            let _ = writeln!(body, "    w[{}] = inputs[{}]; // {}", position, k, name);
+        }
+        for step in schedule.steps() {
+            match *step {
+                Step::Constraint { constraint, variable, inverse } => {
+                    used_constraints[constraint] = true;
+                    let constraint = self.constraint(constraint);
+                    let rest: Vec<(usize, F)> = constraint.c().iter().copied().filter(|(i, _)| *i != variable).collect();
+                    let product = self.product_expression(&mut constants, constraint.a(), constraint.b());
+                    let mut value = if rest.is_empty() { product } else { format!("{} - {}", product, constants.operand(&rest)) };
+                    if inverse != F::one() {
+                        value = format!("{} * {}", operand(value), constants.constant(inverse));
+                    }
+                    let _ = writeln!(body, "    w[{}] = {};", variable, value);
+                }
+                Step::Hint { hint } => {
+                    let (variable, hint) = &self.hints()[hint];
+                    let value = match hint {
+                        Hint::Inverse(x) => format!(
+                            "{}.inverse().ok_or(\"Cannot compute u_{} as the inverse of zero. Is a `!=` constraint violated?\")?",
+                            constants.operand(x.terms()), variable
+                        ),
+                        Hint::Bit { source, index, size } => format!("bit(w[{}], {}, {}, {})?", source, index, size, source),
+                    };
+                    let _ = writeln!(body, "    w[{}] = {};", variable, value);
+                }
+            }
+        }
+        for (r, used) in used_constraints.iter().enumerate() {
+            if *used {
+                continue;
+            }
+            let constraint = self.constraint(r);
+            let product = self.product_expression(&mut constants, constraint.a(), constraint.b());
+            let _ = writeln!(body, "    if {} != {} {{", product, constants.sum(constraint.c()));
+            let _ = writeln!(body, "        return Err(\"Constraint {} is not satisfied by the inputs\".to_string());", r);
+            let _ = writeln!(body, "    }}");
+        }
+        let _ = writeln!(body, "    Ok(w)");
+
+        let mut source = String::new();
+        let _ = writeln!(source, "// A witness calculator generated by r1thm, for a R1CS system with {} constraints and {} variables.", self.size(), self.num_variables());
+        let _ = writeln!(source, "// Use `calculate_witness` to compute the witness from the values of the inputs.\n");
+        let modulus = limbs(-F::one());
+        let modulus = [modulus[0] + 1, modulus[1], modulus[2], modulus[3]];
+        source.push_str(&FIELD_SOURCE.replace("MODULUS_LIMBS", &limbs_literal(modulus)));
+        let _ = writeln!(source, "\n/// The number of variables of the circuit, i.e. the length of the witness.");
+        let _ = writeln!(source, "pub const NUM_VARIABLES: usize = {};\n", self.num_variables());
+        let _ = writeln!(source, "/// The number of inputs of the circuit.");
+        let _ = writeln!(source, "pub const NUM_INPUTS: usize = {};\n", self.input_size());
+        let names: Vec<String> = self.inputs().map(|(name, _)| format!("{:?}", name)).collect();
+        let _ = writeln!(source, "/// The names of the inputs, in the order in which `calculate_witness` takes their values.");
+        let _ = writeln!(source, "pub const INPUTS: [&str; NUM_INPUTS] = [{}];\n", names.join(", "));
+        source.push_str(&constants.declaration());
+        let _ = writeln!(source, "\n/// Computes the witness of the circuit, given the values of the inputs in the order of `INPUTS`.");
+        let _ = writeln!(source, "/// The value of `u_i` is `w[i]` in the returned witness `w`, where `w[0] = 1`. An error is returned");
+        let _ = writeln!(source, "/// if some variable cannot be computed, or if the inputs do not satisfy the constraints.");
+        let _ = writeln!(source, "#[allow(clippy::all, unused_variables)]");
+        let _ = writeln!(source, "pub fn calculate_witness(inputs: &[Fp; NUM_INPUTS]) -> Result<Vec<Fp>, String> {{");
+        source.push_str(&body);
+        source.push_str("}\n");
+        Ok(source)
+    }
+
+    /// Writes the witness calculator of the system (see `witness_calculator`) to the file at `path`,
+    /// e.g. from a build script, after which it can be included by `include!`.
+    pub fn write_witness_calculator<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let source = self.witness_calculator()?;
+        std::fs::write(path.as_ref(), source).map_err(|e| format!("Error writing {}: {}", path.as_ref().display(), e))
+    }
+
+    /// Returns the expression of the product `Σ a_i u_i * Σ b_j u_j`, as Rust code.
+    /// Multiplications by the constant `1` are left out.
+    fn product_expression(&self, constants: &mut Constants<F>, a: &[(usize, F)], b: &[(usize, F)]) -> String {
+        let one = [(0, F::one())];
+        match (a == one, b == one) {
+            (true, _) => constants.sum(b),
+            (_, true) => constants.sum(a),
+            _ => format!("{} * {}", constants.operand(a), constants.operand(b)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::process::Command;
+    use crate::field::Bn254;
+    use crate::parser::{equation2r1cs, poly2r1cs};
+
+    #[test]
+    fn test_generated_source() {
+        let r1cs = poly2r1cs("3x*y + y".to_string(), 10).unwrap();
+        let source = r1cs.witness_calculator().unwrap();
+        assert!(source.contains("pub const INPUTS: [&str; NUM_INPUTS] = [\"x\", \"y\"];"));
+        assert!(source.contains(&format!("pub const NUM_VARIABLES: usize = {};", r1cs.num_variables())));
+        assert!(source.contains("const MODULUS: [u64; 4] = [0x43e1f593f0000001, 0x2833e84879b97091, 0xb85045b68181585d, 0x30644e72e131a029];"));
+        assert!(source.contains("Fp([0x"), "The constants are missing: {}", source);
+        assert!(!source.contains("MODULUS_LIMBS"));
+        // The final constraint is not used to compute a variable, so it is checked.
+        assert!(source.contains("is not satisfied by the inputs"));
+    }
+
+    #[test]
+    fn test_uncomputable_variable() {
+        let mut r1cs = R1CS::<Bn254>::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        let y = r1cs.add_variable();
+        r1cs.add_constraint(crate::R1CSConstraint::new([(y, Bn254::one())].into(), [(y, Bn254::one())].into(), [(x, Bn254::one())].into()));
+        assert!(r1cs.witness_calculator().is_err());
+    }
+
+    /// Compiles the witness calculator of the system together with a `main` computing the witnesses for
+    /// the given assignments, runs it, and checks that it prints the same witnesses as `generate_witness`.
+    fn check_generated_witnesses(r1cs: &R1CS<Bn254>, assignments: &[Vec<u64>]) {
+        let directory = std::env::temp_dir().join(format!("r1thm_codegen_{}_{}", std::process::id(), r1cs.size()));
+        std::fs::create_dir_all(&directory).unwrap();
+        r1cs.write_witness_calculator(directory.join("calculator.rs")).unwrap();
+        let mut main = String::from("include!(\"calculator.rs\");\n\nfn main() {\n");
+        let mut expected = String::new();
+        for assignment in assignments {
+            let values: Vec<String> = assignment.iter().map(|value| format!("Fp::from_u64({})", value)).collect();
+            main.push_str(&format!("    match calculate_witness(&[{}]) {{\n", values.join(", ")));
+            main.push_str("        Ok(w) => println!(\"{:?}\", w),\n        Err(e) => println!(\"{}\", e),\n    }\n");
+            let inputs: HashMap<String, Bn254> = r1cs.inputs()
+                .zip(assignment)
+                .map(|((name, _), value)| (name.to_string(), Bn254::from(*value)))
+                .collect();
+            match r1cs.generate_witness(&inputs) {
+                Ok(witness) => expected.push_str(&format!("{:?}\n", witness)),
+                Err(error) => expected.push_str(&format!("{}\n", error)),
+            }
+        }
+        main.push_str("}\n");
+        std::fs::write(directory.join("main.rs"), main).unwrap();
+        let binary = directory.join("calculator");
+        let compilation = Command::new("rustc")
+            .args(["--edition", "2021", "-o"])
+            .arg(&binary)
+            .arg(directory.join("main.rs"))
+            .output()
+            .expect("Could not run rustc.");
+        assert!(compilation.status.success(), "{}", String::from_utf8_lossy(&compilation.stderr));
+        assert!(compilation.stderr.is_empty(), "The generated code has warnings: {}", String::from_utf8_lossy(&compilation.stderr));
+        let output = Command::new(&binary).output().unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_generated_witnesses() {
+        // Inputs are taken in the order x, y. The second assignment violates `!=`, the last one `<`.
+        let r1cs = equation2r1cs("let t = x*y + 1; t^3 - 2x != 25; bits(y, 4) < 9; (t - y)^2 - (t - y) * (x*y - y + 1) = 0".to_string()).unwrap();
+        assert_eq!(r1cs.inputs().map(|(name, _)| name).collect::<Vec<_>>(), vec!["x", "y"]);
+        check_generated_witnesses(&r1cs, &[vec![3, 2], vec![1, 2], vec![0, 0], vec![2, 11]]);
+    }
+
+    #[test]
+    fn test_generated_witnesses_with_many_bits() {
+        // A decomposition into more than 256 bits has bits beyond the limbs of the values, which are zero.
+        // The gadget `to_bits` rejects such decompositions, so the hints and constraints are added by hand.
+        let mut r1cs = R1CS::<Bn254>::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        let bits: Vec<usize> = (0..300).map(|index| {
+            let bit = r1cs.add_variable();
+            r1cs.add_hint(bit, Hint::Bit { source: x, index, size: 300 });
+            r1cs.add_constraint(crate::R1CSConstraint::new_boolean_constraint(bit));
+            bit
+        }).collect();
+        r1cs.add_constraint(crate::R1CSConstraint::new_bit_decomposition_constraint(&bits, x));
+        check_generated_witnesses(&r1cs, &[vec![5], vec![0]]);
+    }
+}
@@ -0,0 +1,549 @@
+//! This module defines the finite fields over which R1CS systems are evaluated.
+//!
+//! A R1CS constraint `Σ a_i u_i * Σ b_j u_j = Σ c_k u_k` only makes sense once we know
+//! what kind of numbers the coefficients and the variables `u_i` are. In practice these
+//! are elements of a prime field `F_p`, i.e. the integers modulo some large prime `p`.
+//! Working in a field (rather than with plain integers) means that every non-zero value
+//! has an inverse, which is what allows constraints such as `x != 0` to be expressed.
+//!
+//! The field arithmetic is implemented by the generic struct `Fp`, which stores an element
+//! as four 64-bit limbs in Montgomery form. This is the textbook approach, and works for
+//! any odd prime smaller than `2^255`. The concrete fields are given by types implementing
+//! `FieldParameters`, which only need to provide the modulus and a quadratic non-residue. Besides
+//! BN254, the library provides the scalar field of BLS12-381 and the smaller Goldilocks and BabyBear
+//! fields, all of which have roots of unity of large power-of-two orders (see the module `ntt`).
+
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// The operations we need from the field in which a R1CS system is evaluated.
+pub trait Field:
+    Copy
+    + Clone
+    + fmt::Debug
+    + fmt::Display
+    + PartialEq
+    + Eq
+    + Hash
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+    + From<i64>
+    + From<u64>
+{
+    /// Returns the additive identity of the field.
+    fn zero() -> Self;
+
+    /// Returns the multiplicative identity of the field.
+    fn one() -> Self;
+
+    /// Returns the multiplicative inverse of the element, or `None` if the element is zero.
+    fn inverse(&self) -> Option<Self>;
+
+    /// Returns bit number `index` of the smallest non-negative integer representing the element.
+    fn bit(&self, index: usize) -> bool;
+
+    /// Returns the number of bits needed to write the smallest non-negative integer
+    /// representing the element, i.e. the position of its highest set bit plus one.
+    fn bit_length(&self) -> usize;
+
+    /// Returns the number of bits of the modulus of the field.
+    fn modulus_bit_length() -> usize {
+        (-Self::one()).bit_length()
+    }
+
+    /// Checks whether the element is the additive identity.
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+
+    /// Returns a primitive root of unity of order `2^k`, i.e. an element `ω` such that `ω^(2^k) = 1`
+    /// while `ω^(2^(k-1)) != 1`, or `None` if the field has no such element. Since the non-zero
+    /// elements form a cyclic group of order `p - 1`, it exists exactly when `2^k` divides `p - 1`.
+    /// Fields which do not provide roots of unity only return the trivial root `1` for `k = 0`.
+    fn root_of_unity(k: u32) -> Option<Self> {
+        if k == 0 { Some(Self::one()) } else { None }
+    }
+
+    /// Raises the element to the power `exponent` using binary exponentiation.
+    fn pow(&self, mut exponent: u64) -> Self {
+        let mut result = Self::one();
+        let mut base = *self;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+/// The parameters defining a prime field `F_p` implemented by `Fp`.
+pub trait FieldParameters:
+    Copy + Clone + fmt::Debug + Default + PartialEq + Eq + Hash + Send + Sync + 'static
+{
+    /// The prime `p`, given as little-endian 64-bit limbs. It must be odd and smaller than `2^255`.
+    const MODULUS: [u64; 4];
+
+    /// A quadratic non-residue modulo `p`, from which the roots of unity of the field are computed.
+    const NON_RESIDUE: u64;
+}
+
+/// An element of the prime field described by `P`.
+///
+/// The element `x` is stored in Montgomery form, i.e. as `x * 2^256 mod p`. Since the stored
+/// value is always fully reduced, two elements are equal exactly when their limbs are equal.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Fp<P: FieldParameters> {
+    limbs: [u64; 4],
+    parameters: PhantomData<P>,
+}
+
+/// The parameters of the scalar field of the BN254 (also called alt_bn128) elliptic curve.
+/// This is the field used by circom and snarkjs, and is the default field of this library.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bn254Parameters;
+
+impl FieldParameters for Bn254Parameters {
+    // 21888242871839275222246405745257275088548364400416034343698204186575808495617
+    const MODULUS: [u64; 4] = [
+        0x43e1f593f0000001,
+        0x2833e84879b97091,
+        0xb85045b68181585d,
+        0x30644e72e131a029,
+    ];
+    // The multiplicative group is generated by 5, which is thus not a square.
+    const NON_RESIDUE: u64 = 5;
+}
+
+/// The scalar field of the BN254 elliptic curve.
+pub type Bn254 = Fp<Bn254Parameters>;
+
+/// The parameters of the scalar field of the BLS12-381 elliptic curve, used by e.g. Zcash and Ethereum.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bls12_381Parameters;
+
+impl FieldParameters for Bls12_381Parameters {
+    // 52435875175126190479447740508185965837690552500527637822603658699938581184513
+    const MODULUS: [u64; 4] = [
+        0xffffffff00000001,
+        0x53bda402fffe5bfe,
+        0x3339d80809a1d805,
+        0x73eda753299d7d48,
+    ];
+    // The multiplicative group is generated by 7.
+    const NON_RESIDUE: u64 = 7;
+}
+
+/// The scalar field of the BLS12-381 elliptic curve.
+pub type Bls12_381 = Fp<Bls12_381Parameters>;
+
+/// The parameters of the Goldilocks field, of order `2^64 - 2^32 + 1`, used by e.g. Plonky2.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GoldilocksParameters;
+
+impl FieldParameters for GoldilocksParameters {
+    // 18446744069414584321
+    const MODULUS: [u64; 4] = [0xffffffff00000001, 0, 0, 0];
+    // The multiplicative group is generated by 7.
+    const NON_RESIDUE: u64 = 7;
+}
+
+/// The Goldilocks field. Its elements fit in 64 bits, but are stored in four limbs like those of any other `Fp`.
+pub type Goldilocks = Fp<GoldilocksParameters>;
+
+/// The parameters of the BabyBear field, of order `15 * 2^27 + 1`, used by e.g. Plonky3 and RISC Zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BabyBearParameters;
+
+impl FieldParameters for BabyBearParameters {
+    // 2013265921
+    const MODULUS: [u64; 4] = [0x78000001, 0, 0, 0];
+    // The multiplicative group is generated by 31.
+    const NON_RESIDUE: u64 = 31;
+}
+
+/// The BabyBear field. Its elements fit in 31 bits, but are stored in four limbs like those of any other `Fp`.
+pub type BabyBear = Fp<BabyBearParameters>;
+
+
+/// Adds two 256-bit numbers, returning the sum and the carry.
+const fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut result = [0u64; 4];
+    let mut carry = 0u128;
+    let mut i = 0;
+    while i < 4 {
+        let sum = a[i] as u128 + b[i] as u128 + carry;
+        result[i] = sum as u64;
+        carry = sum >> 64;
+        i += 1;
+    }
+    (result, carry != 0)
+}
+
+/// Subtracts two 256-bit numbers, returning the difference and the borrow.
+const fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut result = [0u64; 4];
+    let mut borrow = false;
+    let mut i = 0;
+    while i < 4 {
+        let (difference, borrow_1) = a[i].overflowing_sub(b[i]);
+        let (difference, borrow_2) = difference.overflowing_sub(borrow as u64);
+        result[i] = difference;
+        borrow = borrow_1 || borrow_2;
+        i += 1;
+    }
+    (result, borrow)
+}
+
+/// Checks whether `a >= b` for two 256-bit numbers.
+const fn greater_or_equal(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    let mut i = 4;
+    while i > 0 {
+        i -= 1;
+        if a[i] != b[i] {
+            return a[i] > b[i];
+        }
+    }
+    true
+}
+
+/// Shifts a 256-bit number `n` bits to the right, where `0 < n < 64`.
+const fn shift_right(a: &[u64; 4], n: u32) -> [u64; 4] {
+    let mut result = [0u64; 4];
+    let mut i = 0;
+    while i < 4 {
+        result[i] = a[i] >> n;
+        if i < 3 {
+            result[i] |= a[i + 1] << (64 - n);
+        }
+        i += 1;
+    }
+    result
+}
+
+/// Computes `a + b mod p`, assuming that `a` and `b` are already reduced.
+const fn add_mod(a: &[u64; 4], b: &[u64; 4], p: &[u64; 4]) -> [u64; 4] {
+    let (sum, carry) = add_limbs(a, b);
+    if carry || greater_or_equal(&sum, p) {
+        sub_limbs(&sum, p).0
+    } else {
+        sum
+    }
+}
+
+/// Computes `2^512 mod p` by repeated doubling, which is used to convert into Montgomery form.
+const fn montgomery_r2(p: &[u64; 4]) -> [u64; 4] {
+    let mut r = [1u64, 0, 0, 0];
+    let mut i = 0;
+    while i < 512 {
+        r = add_mod(&r, &r, p);
+        i += 1;
+    }
+    r
+}
+
+/// Computes `-p^{-1} mod 2^64`, which is needed for Montgomery reduction.
+const fn montgomery_inv(p: &[u64; 4]) -> u64 {
+    let mut inv = 1u64;
+    let mut i = 0;
+    while i < 63 {
+        inv = inv.wrapping_mul(inv);
+        inv = inv.wrapping_mul(p[0]);
+        i += 1;
+    }
+    inv.wrapping_neg()
+}
+
+
+impl<P: FieldParameters> Fp<P> {
+    const R2: [u64; 4] = montgomery_r2(&P::MODULUS);
+    const INV: u64 = montgomery_inv(&P::MODULUS);
+
+    /// Creates an element from a number given as little-endian 64-bit limbs.
+    /// The number does not have to be smaller than the modulus; it is reduced.
+    pub fn from_limbs(limbs: [u64; 4]) -> Self {
+        Self::from_montgomery(Self::montgomery_multiplication(&limbs, &Self::R2))
+    }
+
+    /// Returns the smallest non-negative integer representing the element,
+    /// as little-endian 64-bit limbs.
+    pub fn to_limbs(self) -> [u64; 4] {
+        Self::montgomery_multiplication(&self.limbs, &[1, 0, 0, 0])
+    }
+
+    /// Raises the element to the power `exponent`, given as little-endian 64-bit limbs.
+    fn pow_limbs(&self, exponent: &[u64; 4]) -> Self {
+        let mut result = Self::one();
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result * result;
+                if (limb >> bit) & 1 == 1 {
+                    result = result * *self;
+                }
+            }
+        }
+        result
+    }
+
+    fn from_montgomery(limbs: [u64; 4]) -> Self {
+        Fp { limbs, parameters: PhantomData }
+    }
+
+    /// Computes `a * b * 2^-256 mod p` using the CIOS method.
+    fn montgomery_multiplication(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let p = &P::MODULUS;
+        let mut t = [0u64; 6];
+        for b_i in b.iter() {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let product = t[j] as u128 + a[j] as u128 * *b_i as u128 + carry;
+                t[j] = product as u64;
+                carry = product >> 64;
+            }
+            let sum = t[4] as u128 + carry;
+            t[4] = sum as u64;
+            t[5] = (sum >> 64) as u64;
+
+            let m = t[0].wrapping_mul(Self::INV);
+            let mut carry = (t[0] as u128 + m as u128 * p[0] as u128) >> 64;
+            for j in 1..4 {
+                let product = t[j] as u128 + m as u128 * p[j] as u128 + carry;
+                t[j - 1] = product as u64;
+                carry = product >> 64;
+            }
+            let sum = t[4] as u128 + carry;
+            t[3] = sum as u64;
+            t[4] = t[5] + (sum >> 64) as u64;
+        }
+        let result = [t[0], t[1], t[2], t[3]];
+        if t[4] != 0 || greater_or_equal(&result, p) {
+            sub_limbs(&result, p).0
+        } else {
+            result
+        }
+    }
+}
+
+impl<P: FieldParameters> Field for Fp<P> {
+    fn zero() -> Self {
+        Self::from_montgomery([0; 4])
+    }
+
+    fn one() -> Self {
+        Self::from_limbs([1, 0, 0, 0])
+    }
+
+    /// Computes the inverse using Fermat's little theorem, i.e. as `x^(p - 2)`.
+    fn inverse(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        Some(self.pow_limbs(&sub_limbs(&P::MODULUS, &[2, 0, 0, 0]).0))
+    }
+
+    /// Writing `p - 1 = 2^s t` with `t` odd, the element `g^t` for the non-residue `g` has order
+    /// `2^s`, and is squared `s - k` times to get a root of order `2^k`.
+    fn root_of_unity(k: u32) -> Option<Self> {
+        let mut exponent = sub_limbs(&P::MODULUS, &[1, 0, 0, 0]).0;
+        let mut two_adicity = 0;
+        while exponent[0] & 1 == 0 {
+            exponent = shift_right(&exponent, 1);
+            two_adicity += 1;
+        }
+        if k > two_adicity {
+            return None;
+        }
+        let mut root = Self::from(P::NON_RESIDUE).pow_limbs(&exponent);
+        for _ in k..two_adicity {
+            root = root * root;
+        }
+        Some(root)
+    }
+
+    fn bit(&self, index: usize) -> bool {
+        index < 256 && (self.to_limbs()[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    fn bit_length(&self) -> usize {
+        let limbs = self.to_limbs();
+        match limbs.iter().rposition(|limb| *limb != 0) {
+            Some(i) => 64 * i + 64 - limbs[i].leading_zeros() as usize,
+            None => 0,
+        }
+    }
+}
+
+impl<P: FieldParameters> Add for Fp<P> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::from_montgomery(add_mod(&self.limbs, &other.limbs, &P::MODULUS))
+    }
+}
+
+impl<P: FieldParameters> Sub for Fp<P> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        let (difference, borrow) = sub_limbs(&self.limbs, &other.limbs);
+        if borrow {
+            Self::from_montgomery(add_limbs(&difference, &P::MODULUS).0)
+        } else {
+            Self::from_montgomery(difference)
+        }
+    }
+}
+
+impl<P: FieldParameters> Mul for Fp<P> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::from_montgomery(Self::montgomery_multiplication(&self.limbs, &other.limbs))
+    }
+}
+
+impl<P: FieldParameters> Neg for Fp<P> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::zero() - self
+    }
+}
+
+impl<P: FieldParameters> From<u64> for Fp<P> {
+    fn from(value: u64) -> Self {
+        Self::from_limbs([value, 0, 0, 0])
+    }
+}
+
+impl<P: FieldParameters> From<i64> for Fp<P> {
+    fn from(value: i64) -> Self {
+        let magnitude = Self::from(value.unsigned_abs());
+        if value < 0 {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+}
+
+/// Prints the element as the smallest non-negative integer representing it, in base 10.
+impl<P: FieldParameters> fmt::Display for Fp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut limbs = self.to_limbs();
+        let mut digits = Vec::new();
+        loop {
+            let mut remainder = 0u128;
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 64) | *limb as u128;
+                *limb = (current / 10) as u64;
+                remainder = current % 10;
+            }
+            digits.push(char::from(b'0' + remainder as u8));
+            if limbs == [0; 4] {
+                break;
+            }
+        }
+        let digits: String = digits.iter().rev().collect();
+        f.pad_integral(true, "", &digits)
+    }
+}
+
+impl<P: FieldParameters> fmt::Debug for Fp<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_arithmetic() {
+        let x = Bn254::from(6u64);
+        let y = Bn254::from(7u64);
+        assert_eq!(x + y, Bn254::from(13u64));
+        assert_eq!(x * y, Bn254::from(42u64));
+        assert_eq!(y - x, Bn254::one());
+        assert_eq!(x - y, Bn254::from(-1i64));
+        assert_eq!(x.pow(3), Bn254::from(216u64));
+    }
+
+    #[test]
+    fn test_wraparound() {
+        let minus_one = -Bn254::one();
+        assert_eq!(minus_one + Bn254::one(), Bn254::zero());
+        assert_eq!(minus_one * minus_one, Bn254::one());
+        assert_eq!(minus_one.to_string(), "21888242871839275222246405745257275088548364400416034343698204186575808495616");
+        assert_eq!(Bn254::from_limbs(Bn254Parameters::MODULUS), Bn254::zero());
+    }
+
+    #[test]
+    fn test_inverse() {
+        assert_eq!(Bn254::zero().inverse(), None);
+        for value in [1i64, 2, 3, -5, 123456789] {
+            let x = Bn254::from(value);
+            assert_eq!(x * x.inverse().unwrap(), Bn254::one());
+        }
+    }
+
+    #[test]
+    fn test_bits() {
+        let x = Bn254::from(0b1011u64);
+        assert!(x.bit(0) && x.bit(1) && !x.bit(2) && x.bit(3) && !x.bit(4));
+        assert_eq!(x.bit_length(), 4);
+        assert_eq!(Bn254::zero().bit_length(), 0);
+        assert_eq!((-Bn254::one()).bit_length(), 254);
+        assert_eq!(Bn254::modulus_bit_length(), 254);
+        assert_eq!(Bn254::from(u64::MAX).pow(2).bit_length(), 128);
+    }
+
+    #[test]
+    fn test_roots_of_unity() {
+        let root = Bn254::root_of_unity(28).unwrap();
+        assert_eq!(root.pow(1 << 27), -Bn254::one());
+        assert_eq!(root.pow(1 << 28), Bn254::one());
+        assert_eq!(Bn254::root_of_unity(29), None);
+        assert_eq!(Bn254::root_of_unity(3).unwrap(), root.pow(1 << 25));
+        assert_eq!(Bn254::root_of_unity(0), Some(Bn254::one()));
+        assert_eq!(Bn254::root_of_unity(1), Some(-Bn254::one()));
+    }
+
+    /// Checks that the field has the given modulus and two-adicity `s`, and that its root of unity of order `2^s` is primitive.
+    fn check_field<F: Field>(modulus_minus_one: &str, s: u32) {
+        assert_eq!((-F::one()).to_string(), modulus_minus_one);
+        let root = F::root_of_unity(s).unwrap();
+        assert_eq!(root.pow(1 << (s - 1)), -F::one());
+        assert_eq!(root.pow(1 << s), F::one());
+        assert_eq!(F::root_of_unity(s + 1), None);
+    }
+
+    #[test]
+    fn test_other_fields() {
+        check_field::<Bn254>("21888242871839275222246405745257275088548364400416034343698204186575808495616", 28);
+        check_field::<Bls12_381>("52435875175126190479447740508185965837690552500527637822603658699938581184512", 32);
+        check_field::<Goldilocks>("18446744069414584320", 32);
+        check_field::<BabyBear>("2013265920", 27);
+        assert_eq!(Goldilocks::from(u64::MAX), Goldilocks::from((1u64 << 32) - 2));
+        assert_eq!(BabyBear::from(2013265921u64 + 5), BabyBear::from(5u64));
+        assert_eq!(BabyBear::from(7u64).inverse().unwrap() * BabyBear::from(7u64), BabyBear::one());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Bn254::zero().to_string(), "0");
+        assert_eq!(Bn254::from(1234567890123456789u64).to_string(), "1234567890123456789");
+        assert_eq!(Bn254::from(u64::MAX).pow(2).to_string(), "340282366920938463426481119284349108225");
+    }
+}
@@ -0,0 +1,257 @@
+//! This module implements *gadgets*, i.e. small building blocks which append constraints
+//! (and, when needed, hints for witness generation) to an existing R1CS system.
+//!
+//! Polynomials alone cannot express restrictions such as "`x` is a bit" or "`x` fits in
+//! eight bits", since every value in the field is a valid input to a polynomial. The gadgets
+//! in this module add the constraints needed to express such restrictions. They work on
+//! variables given by their indices, and return the indices of any new variables.
+
+use crate::field::Field;
+use crate::r1cs::R1CS;
+use crate::r1cs_constraint::R1CSConstraint;
+use crate::witness::Hint;
+
+/// Constrains the variable `u_i` to be boolean, i.e. to be either `0` or `1`.
+///
+/// This adds the constraint `u_i * (1 - u_i) = 0`.
+pub fn assert_boolean<F: Field>(r1cs: &mut R1CS<F>, i: usize) {
+    r1cs.add_constraint(R1CSConstraint::new_boolean_constraint(i));
+}
+
+/// Decomposes the variable `u_i` into `n` new boolean variables, which are returned with
+/// the least significant bit first.
+///
+/// The constraints state that each new variable is boolean and that their weighted sum
+/// `Σ 2^j u_{bit_j}` equals `u_i`. Hence, the system can only be satisfied if `u_i` fits in `n`
+/// bits. Witness generation computes the bits from hints.
+///
+/// The decomposition is only unique if `2^n` is smaller than the modulus of the field. For larger `n`,
+/// the weighted sum may wrap around the modulus, and the constraints would not restrict `u_i` at all.
+///
+/// # Panics
+/// Panics if `n` is not smaller than `F::modulus_bit_length()`.
+pub fn to_bits<F: Field>(r1cs: &mut R1CS<F>, i: usize, n: usize) -> Vec<usize> {
+    assert!(n < F::modulus_bit_length(), "Cannot decompose values into {} bits in a field with a {} bit modulus.", n, F::modulus_bit_length());
+    let mut bits = Vec::with_capacity(n);
+    for index in 0..n {
+        let bit = r1cs.add_variable();
+        r1cs.add_hint(bit, Hint::Bit { source: i, index, size: n });
+        assert_boolean(r1cs, bit);
+        bits.push(bit);
+    }
+    r1cs.add_constraint(R1CSConstraint::new_bit_decomposition_constraint(&bits, i));
+    bits
+}
+
+/// Constrains the variable `u_i` to fit in `n` bits, i.e. to be smaller than `2^n`.
+///
+/// This is done by decomposing `u_i` into bits using `to_bits`, ignoring the resulting bits.
+///
+/// # Panics
+/// Panics if `n` is not smaller than `F::modulus_bit_length()`, since every value would then fit.
+pub fn assert_range<F: Field>(r1cs: &mut R1CS<F>, i: usize, n: usize) {
+    to_bits(r1cs, i, n);
+}
+
+/// Creates a new boolean variable which is `1` if `u_i < u_j` and `0` otherwise,
+/// and returns its index.
+///
+/// The values of `u_i` and `u_j` are assumed to fit in `n` bits, where `n + 1` must be smaller
+/// than the number of bits of the modulus. The gadget does not add constraints restricting `u_i`
+/// and `u_j`, so unless they are already restricted (e.g. using `assert_range`), the result is meaningless.
+///
+/// # Panics
+/// Panics if `n + 1` is not smaller than `F::modulus_bit_length()`, e.g. for `n = 30` over `BabyBear`.
+pub fn less_than<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize, n: usize) -> usize {
+    // u_i < u_j exactly when u_i + 1 <= u_j.
+    compare(r1cs, i, j, F::from(2u64).pow(n as u64) - F::one(), n)
+}
+
+/// Creates a new boolean variable which is `1` if `u_i <= u_j` and `0` otherwise,
+/// and returns its index.
+///
+/// The values of `u_i` and `u_j` are assumed to fit in `n` bits, just as for `less_than`.
+///
+/// # Panics
+/// Panics if `n + 1` is not smaller than `F::modulus_bit_length()`, just as `less_than`.
+pub fn less_or_equal<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize, n: usize) -> usize {
+    compare(r1cs, i, j, F::from(2u64).pow(n as u64), n)
+}
+
+/// Computes the variable `d = u_j - u_i + s` and decomposes it into `n + 1` bits, returning the
+/// most significant one. When `s = 2^n` and `u_i` and `u_j` fit in `n` bits, `d` fits in `n + 1`
+/// bits, and its most significant bit is set exactly when `u_i <= u_j`.
+fn compare<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize, s: F, n: usize) -> usize {
+    assert!(n + 1 < F::modulus_bit_length(), "Cannot compare values with {} bits in a field with a {} bit modulus.", n, F::modulus_bit_length());
+    let difference = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_shifted_difference_constraint(s, j, i, difference));
+    to_bits(r1cs, difference, n + 1)[n]
+}
+
+/// Creates a new variable whose value is the number with the binary representation given by
+/// the variables `bits` (least significant bit first), and returns its index.
+///
+/// This function does not constrain the variables in `bits` to be boolean. If they are
+/// not already constrained, use `assert_boolean` on each of them.
+pub fn from_bits<F: Field>(r1cs: &mut R1CS<F>, bits: &[usize]) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_bit_decomposition_constraint(bits, variable));
+    variable
+}
+
+/// Creates a new variable which is `u_i and u_j`, i.e. the product `u_i * u_j`, and returns its index.
+///
+/// The variables `u_i` and `u_j` are assumed to be boolean. This gadget does not constrain them to be,
+/// so unless they are already constrained (e.g. using `assert_boolean`), the result is meaningless.
+/// The same holds for the other boolean operators `boolean_or`, `boolean_xor` and `boolean_not`.
+pub fn boolean_and<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(i, j, variable));
+    variable
+}
+
+/// Creates a new variable which is `u_i or u_j` for boolean variables `u_i` and `u_j`, and returns its index.
+pub fn boolean_or<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_disjunction_constraint(i, j, variable));
+    variable
+}
+
+/// Creates a new variable which is `u_i xor u_j` for boolean variables `u_i` and `u_j`, and returns its index.
+pub fn boolean_xor<F: Field>(r1cs: &mut R1CS<F>, i: usize, j: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_exclusive_disjunction_constraint(i, j, variable));
+    variable
+}
+
+/// Creates a new variable which is `not u_i`, i.e. `1 - u_i`, for a boolean variable `u_i`, and returns its index.
+pub fn boolean_not<F: Field>(r1cs: &mut R1CS<F>, i: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_shifted_difference_constraint(F::zero(), 0, i, variable));
+    variable
+}
+
+/// Creates a new variable which is `u_i` if `u_b = 1` and `u_j` if `u_b = 0`, and returns its index.
+///
+/// This is lowered to the single constraint `u_b * (u_i - u_j) = u_k - u_j` for the new variable `u_k`.
+/// The variable `u_b` is assumed to be boolean, just as for the boolean operators.
+pub fn select<F: Field>(r1cs: &mut R1CS<F>, b: usize, i: usize, j: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_selection_constraint(b, i, j, variable));
+    variable
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::field::Bn254;
+
+    /// Creates a system with a single input `x`, returning the system and the index of `x`.
+    fn system_with_input() -> (R1CS, usize) {
+        let mut r1cs = R1CS::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        (r1cs, x)
+    }
+
+    /// Generates a witness for a system with the single input `x`.
+    fn witness(r1cs: &R1CS, x: i64) -> Result<Vec<Bn254>, String> {
+        r1cs.generate_witness(&HashMap::from([("x".to_string(), Bn254::from(x))]))
+    }
+
+    #[test]
+    fn test_assert_boolean() {
+        let (mut r1cs, x) = system_with_input();
+        assert_boolean(&mut r1cs, x);
+        assert!(witness(&r1cs, 0).is_ok());
+        assert!(witness(&r1cs, 1).is_ok());
+        assert!(witness(&r1cs, 2).is_err());
+        assert!(witness(&r1cs, -1).is_err());
+    }
+
+    #[test]
+    fn test_to_bits() {
+        let (mut r1cs, x) = system_with_input();
+        let bits = to_bits(&mut r1cs, x, 8);
+        assert_eq!(bits.len(), 8);
+        assert_eq!(r1cs.size(), 9);
+        let values = witness(&r1cs, 0b10110001).unwrap();
+        let bit_values: Vec<Bn254> = bits.iter().map(|bit| values[*bit]).collect();
+        let expected: Vec<Bn254> = [1u64, 0, 0, 0, 1, 1, 0, 1].iter().map(|bit| Bn254::from(*bit)).collect();
+        assert_eq!(bit_values, expected);
+        assert!(witness(&r1cs, 255).is_ok());
+        let error = witness(&r1cs, 256).unwrap_err();
+        assert!(error.contains("does not fit in 8 bits"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_from_bits() {
+        let (mut r1cs, x) = system_with_input();
+        let bits = to_bits(&mut r1cs, x, 4);
+        let reversed: Vec<usize> = bits.iter().rev().copied().collect();
+        let y = from_bits(&mut r1cs, &reversed);
+        let values = witness(&r1cs, 0b0011).unwrap();
+        assert_eq!(values[y], Bn254::from(0b1100u64));
+    }
+
+    #[test]
+    fn test_assert_range() {
+        let (mut r1cs, x) = system_with_input();
+        assert_range(&mut r1cs, x, 5);
+        assert!(witness(&r1cs, 31).is_ok());
+        assert!(witness(&r1cs, 32).is_err());
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut r1cs = R1CS::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        let y = r1cs.add_input_variable("y".to_string());
+        let lt = less_than(&mut r1cs, x, y, 4);
+        let le = less_or_equal(&mut r1cs, x, y, 4);
+        for (x_value, y_value) in [(0, 0), (3, 4), (4, 3), (15, 15), (0, 15), (15, 0), (7, 8)] {
+            let inputs = HashMap::from([("x".to_string(), Bn254::from(x_value as u64)), ("y".to_string(), Bn254::from(y_value as u64))]);
+            let values = r1cs.generate_witness(&inputs).unwrap();
+            assert_eq!(values[lt], Bn254::from((x_value < y_value) as u64), "{} < {}", x_value, y_value);
+            assert_eq!(values[le], Bn254::from((x_value <= y_value) as u64), "{} <= {}", x_value, y_value);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot decompose values into 254 bits in a field with a 254 bit modulus.")]
+    fn test_too_many_bits() {
+        let (mut r1cs, x) = system_with_input();
+        to_bits(&mut r1cs, x, 254);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot compare values with 30 bits in a field with a 31 bit modulus.")]
+    fn test_comparison_too_large_for_field() {
+        let mut r1cs = R1CS::<crate::field::BabyBear>::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        let y = r1cs.add_input_variable("y".to_string());
+        less_than(&mut r1cs, x, y, 30);
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let mut r1cs = R1CS::new();
+        let x = r1cs.add_input_variable("x".to_string());
+        let y = r1cs.add_input_variable("y".to_string());
+        let and = boolean_and(&mut r1cs, x, y);
+        let or = boolean_or(&mut r1cs, x, y);
+        let xor = boolean_xor(&mut r1cs, x, y);
+        let not = boolean_not(&mut r1cs, x);
+        let selection = select(&mut r1cs, x, and, not);
+        for (x_value, y_value) in [(false, false), (false, true), (true, false), (true, true)] {
+            let inputs = HashMap::from([("x".to_string(), Bn254::from(x_value as u64)), ("y".to_string(), Bn254::from(y_value as u64))]);
+            let values = r1cs.generate_witness(&inputs).unwrap();
+            assert_eq!(values[and], Bn254::from((x_value && y_value) as u64));
+            assert_eq!(values[or], Bn254::from((x_value || y_value) as u64));
+            assert_eq!(values[xor], Bn254::from((x_value ^ y_value) as u64));
+            assert_eq!(values[not], Bn254::from((!x_value) as u64));
+            assert_eq!(values[selection], Bn254::from((if x_value { x_value && y_value } else { !x_value }) as u64));
+        }
+    }
+}
@@ -1,7 +1,32 @@
+mod builder;
+mod codegen;
+mod field;
+mod gadgets;
+mod linear_combination;
+mod lowering;
+mod ntt;
+mod parallel;
 mod parser;
+mod polynomial;
+mod qap;
 mod r1cs;
 mod r1cs_constraint;
+#[cfg(feature = "serde")]
+mod snarkjs;
+mod sparse_matrix;
+mod streaming;
+mod text_format;
+mod witness;
 
-pub use parser::poly2r1cs;
+pub use builder::{ConstraintSystem, Variable};
+pub use field::{BabyBear, Bls12_381, Bn254, Field, Goldilocks};
+pub use gadgets::{assert_boolean, assert_range, boolean_and, boolean_not, boolean_or, boolean_xor, from_bits, less_or_equal, less_than, select, to_bits};
+pub use linear_combination::LinearCombination;
+pub use ntt::{inverse_ntt, ntt};
+pub use parser::{equation2r1cs, poly2r1cs, poly2r1cs_file};
+pub use polynomial::UnivariatePolynomial;
+pub use qap::QAP;
 pub use r1cs::R1CS;
-
+pub use r1cs_constraint::R1CSConstraint;
+pub use streaming::{poly2r1cs_reader, poly2r1cs_streaming};
+pub use witness::{WitnessBatch, WitnessSchedule};
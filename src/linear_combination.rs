@@ -0,0 +1,245 @@
+//! This module defines the LinearCombination struct, representing a sum `Σ x_i u_i` of variables
+//! with coefficients in a field.
+//!
+//! Each of the three sums in a R1CS constraint `Σ a_i u_i * Σ b_j u_j = Σ c_k u_k` is a linear
+//! combination. As in the rest of the library, a variable `u_i` is identified by its index `i`,
+//! and `u_0` is the constant `1`, so that the constant `s` is the linear combination `s u_0`.
+//!
+//! A linear combination only stores the variables with a non-zero coefficient: adding terms
+//! with the same variable merges them, and terms which cancel out are removed. The terms are
+//! kept in a vector sorted by the index of their variable, so that iterating over them is
+//! deterministic. Since most sums in a system have only one or two terms, a sorted vector is
+//! much more compact than a map, and the terms can be copied directly into the flat storage of
+//! a R1CS system (see the module `sparse_matrix`).
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::field::{Bn254, Field};
+
+/// A linear combination `Σ x_i u_i`, given by the pairs `(i, x_i)` with non-zero coefficients `x_i`, sorted by `i`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinearCombination<F: Field = Bn254> {
+    terms: Vec<(usize, F)>,
+}
+
+impl<F: Field> Default for LinearCombination<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> LinearCombination<F> {
+    /// Creates the empty linear combination, i.e. `0`.
+    pub fn new() -> Self {
+        LinearCombination {
+            terms: Vec::new(),
+        }
+    }
+
+    /// Creates the linear combination consisting of the single variable `u_i`.
+    pub fn variable(i: usize) -> Self {
+        Self::from([(i, F::one())])
+    }
+
+    /// Creates the linear combination of the constant `s`, i.e. `s u_0`.
+    pub fn constant(s: F) -> Self {
+        Self::from([(0, s)])
+    }
+
+    /// Adds the term `s u_i`. If `u_i` already appears, the coefficients are added together,
+    /// and the variable is removed if the sum of its coefficients is zero.
+    ///
+    /// Terms are usually added in order of increasing `i`, in which case the term is appended in constant time.
+    pub fn add_term(&mut self, i: usize, s: F) {
+        if s.is_zero() {
+            return;
+        }
+        if self.terms.last().is_none_or(|(last, _)| *last < i) {
+            self.terms.push((i, s));
+            return;
+        }
+        match self.terms.binary_search_by_key(&i, |(j, _)| *j) {
+            Ok(position) => {
+                let coefficient = self.terms[position].1 + s;
+                if coefficient.is_zero() {
+                    self.terms.remove(position);
+                } else {
+                    self.terms[position].1 = coefficient;
+                }
+            }
+            Err(position) => self.terms.insert(position, (i, s)),
+        }
+    }
+
+    /// Returns the coefficient of `u_i`, which is zero if the variable does not appear.
+    pub fn coefficient(&self, i: usize) -> F {
+        coefficient(&self.terms, i)
+    }
+
+    /// Returns the terms `(i, x_i)` with non-zero coefficients, ordered by the index of the variable.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, F)> + '_ {
+        self.terms.iter().copied()
+    }
+
+    /// Returns the indices of the variables appearing in the linear combination, in increasing order.
+    pub fn variables(&self) -> impl Iterator<Item = usize> + '_ {
+        self.terms.iter().map(|(i, _)| *i)
+    }
+
+    /// Returns the terms `(i, x_i)` as a slice sorted by `i`.
+    pub(crate) fn terms(&self) -> &[(usize, F)] {
+        &self.terms
+    }
+
+    /// Returns the number of variables with a non-zero coefficient.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Checks whether the linear combination is `0`, i.e. has no terms.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Returns the index `i` if the linear combination is the single variable `u_i`.
+    pub fn as_variable(&self) -> Option<usize> {
+        match self.terms[..] {
+            [(i, x)] if x == F::one() => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the linear combination given the values of the variables, where `values[i]` is the value of `u_i`.
+    pub fn evaluate(&self, values: &[F]) -> F {
+        evaluate(&self.terms, values)
+    }
+}
+
+/// Returns the coefficient of `u_i` in the sum given by the terms `(i, x_i)`, sorted by `i`.
+pub(crate) fn coefficient<F: Field>(terms: &[(usize, F)], i: usize) -> F {
+    match terms.binary_search_by_key(&i, |(j, _)| *j) {
+        Ok(position) => terms[position].1,
+        Err(_) => F::zero(),
+    }
+}
+
+/// Evaluates the sum `Σ x_i u_i` given by the terms `(i, x_i)`, where `values[i]` is the value of `u_i`.
+pub(crate) fn evaluate<F: Field>(terms: &[(usize, F)], values: &[F]) -> F {
+    terms.iter().fold(F::zero(), |sum, (i, x)| sum + *x * values[*i])
+}
+
+/// Evaluates the sum `Σ x_i u_i` given by the terms `(i, x_i)` for a batch of `n` assignments, stored
+/// column by column: the values of `u_i` in the assignments are `values[i * n..(i + 1) * n]`.
+pub(crate) fn evaluate_batch<F: Field>(terms: &[(usize, F)], values: &[F], n: usize) -> Vec<F> {
+    let mut sums = vec![F::zero(); n];
+    for (i, x) in terms {
+        for (sum, value) in sums.iter_mut().zip(&values[i * n..(i + 1) * n]) {
+            *sum = *sum + *x * *value;
+        }
+    }
+    sums
+}
+
+impl<F: Field, const N: usize> From<[(usize, F); N]> for LinearCombination<F> {
+    /// Creates the linear combination `Σ x_i u_i` from an array of pairs `(i, x_i)`, merging the
+    /// terms with the same variable.
+    fn from(terms: [(usize, F); N]) -> Self {
+        terms.into_iter().collect()
+    }
+}
+
+impl<F: Field> FromIterator<(usize, F)> for LinearCombination<F> {
+    fn from_iter<I: IntoIterator<Item = (usize, F)>>(terms: I) -> Self {
+        let mut linear_combination = LinearCombination::new();
+        for (i, x) in terms {
+            linear_combination.add_term(i, x);
+        }
+        linear_combination
+    }
+}
+
+impl<F: Field> Add for LinearCombination<F> {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        for (i, x) in other.terms {
+            self.add_term(i, x);
+        }
+        self
+    }
+}
+
+impl<F: Field> Sub for LinearCombination<F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl<F: Field> Neg for LinearCombination<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -F::one()
+    }
+}
+
+impl<F: Field> Mul<F> for LinearCombination<F> {
+    type Output = Self;
+
+    /// Scales every coefficient by `s`. Scaling by zero gives the empty linear combination.
+    fn mul(mut self, s: F) -> Self {
+        if s.is_zero() {
+            return LinearCombination::new();
+        }
+        for (_, x) in self.terms.iter_mut() {
+            *x = *x * s;
+        }
+        self
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r1cs_constraint::R1CSConstraint;
+
+    fn f(value: i64) -> Bn254 {
+        Bn254::from(value)
+    }
+
+    #[test]
+    fn test_terms_are_merged_and_pruned() {
+        let mut sum = LinearCombination::from([(3, f(2)), (1, f(1)), (3, f(5))]);
+        assert_eq!(sum.iter().collect::<Vec<_>>(), vec![(1, f(1)), (3, f(7))]);
+        sum.add_term(1, f(-1));
+        assert_eq!(sum.len(), 1);
+        assert_eq!(sum.coefficient(1), f(0));
+        assert_eq!(sum.coefficient(3), f(7));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let x = LinearCombination::variable(1);
+        let y = LinearCombination::variable(2);
+        let sum = (x.clone() + y.clone()) * f(3) - x.clone() + LinearCombination::constant(f(4));
+        assert_eq!(sum, LinearCombination::from([(0, f(4)), (1, f(2)), (2, f(3))]));
+        assert_eq!(sum.evaluate(&[f(1), f(10), f(100)]), f(324));
+        assert!((sum.clone() - sum).is_empty());
+        assert!((x.clone() * f(0)).is_empty());
+        assert_eq!(x.as_variable(), Some(1));
+        assert_eq!((-x).as_variable(), None);
+    }
+
+    #[test]
+    fn test_constraint_from_linear_combinations() {
+        // (x + 2y) * (3 - x) = z
+        let a = LinearCombination::from([(1, f(1)), (2, f(2))]);
+        let b = LinearCombination::constant(f(3)) - LinearCombination::variable(1);
+        let constraint = R1CSConstraint::new(a, b, LinearCombination::variable(3));
+        assert!(constraint.view().is_satisfied(&[f(1), f(1), f(2), f(10)]));
+        assert!(!constraint.view().is_satisfied(&[f(1), f(1), f(2), f(11)]));
+    }
+}
@@ -0,0 +1,103 @@
+//! This module implements the lowering of arithmetic to R1CS constraints, shared by the
+//! front-ends of the library: the polynomial parser and the `ConstraintSystem` builder.
+//!
+//! Each function appends the constraints for a single operation to an existing R1CS system,
+//! and returns the index of the variable holding the result. Since both front-ends use these
+//! functions, they produce identical systems for identical expressions.
+
+use crate::field::Field;
+use crate::linear_combination::LinearCombination;
+use crate::r1cs::R1CS;
+use crate::r1cs_constraint::R1CSConstraint;
+use crate::witness::Hint;
+
+/// Creates a new variable `u_k` for the constant `s`, with the constraint `1 * s = u_k`.
+pub(crate) fn constant<F: Field>(r1cs: &mut R1CS<F>, s: F) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_constant_constraint(s, variable));
+    variable
+}
+
+/// Creates a new variable `u_k` for the product `s * u_j`, with the constraint `s * u_j = u_k`.
+pub(crate) fn scale<F: Field>(r1cs: &mut R1CS<F>, s: F, j: usize) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_constant_multiplication_constraint(s, j, variable));
+    variable
+}
+
+/// Creates a new variable `u_k` for the linear combination `sum`, with the constraint `sum = u_k`.
+pub(crate) fn linear_combination<F: Field>(r1cs: &mut R1CS<F>, sum: LinearCombination<F>) -> usize {
+    let variable = r1cs.add_variable();
+    r1cs.add_constraint(R1CSConstraint::new_linear_combination_constraint(sum, variable));
+    variable
+}
+
+/// Creates constraints for the product of the given variables, multiplying them one at a time from
+/// the left, and returns the index of the variable of the product. The empty product is `u_0 = 1`.
+pub(crate) fn product<F: Field>(r1cs: &mut R1CS<F>, variables: &[usize]) -> usize {
+    let mut current_variable = match variables.first() {
+        Some(variable) => *variable,
+        None => return 0,
+    };
+    for variable in variables.iter().skip(1) {
+        let product_variable = r1cs.add_variable();
+        r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(current_variable, *variable, product_variable));
+        current_variable = product_variable;
+    }
+    current_variable
+}
+
+/// Creates constraints from exponentiations, returning the variable of `u_i^exponent`.
+///
+/// Note that for any exponentiation where the power is larger than 2, we need
+/// to create multiple constraints. We do this using binary exponentiation, in order
+/// to keep the number of constraints low.
+///
+/// Constraints created by this function are of the form `u_i * u_j = u_k`,
+/// where `u_i` and `u_j` are powers of the variable and `u_k` is a new power.
+///
+/// **Note**: This function ignores checking whether there is already a varible for
+/// any of the intermediate powers. This is a potential optimization (if optimizing for
+/// a minimal number of variables; the effect on performace would depend on the input
+/// polynomial), but has not been implemented yet.
+pub(crate) fn power<F: Field>(r1cs: &mut R1CS<F>, i: usize, mut exponent: u32) -> usize {
+    if exponent == 0 {
+        0
+    } else if exponent == 1 {
+        i
+    } else {
+        let max_exponent_variable = r1cs.add_variable();
+        let mut current_exponent_variable = max_exponent_variable;
+        while exponent > 1 {
+            if exponent.is_multiple_of(2) {
+                exponent /= 2;
+                if exponent == 1 {
+                    r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(i, i, current_exponent_variable));
+                } else {
+                    let new_exponent_variable = r1cs.add_variable();
+                    r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(new_exponent_variable, new_exponent_variable, current_exponent_variable));
+                    current_exponent_variable = new_exponent_variable;
+                }
+            } else {
+                let new_exponent_variable = r1cs.add_variable();
+                r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(i, new_exponent_variable, current_exponent_variable));
+                exponent -= 1;
+                current_exponent_variable = new_exponent_variable;
+            }
+        }
+        max_exponent_variable
+    }
+}
+
+/// Adds the constraint `u_i = s`, or the constraint `(u_i - s) * u_k = 1` for a new variable `u_k`
+/// if `is_inequality` is set. The latter can be satisfied exactly when `u_i - s` has an inverse,
+/// i.e. when `u_i != s`. Witness generation computes `u_k` from a hint.
+pub(crate) fn assert_equation<F: Field>(r1cs: &mut R1CS<F>, i: usize, s: F, is_inequality: bool) {
+    if is_inequality {
+        let inverse_variable = r1cs.add_variable();
+        r1cs.add_hint(inverse_variable, Hint::Inverse(LinearCombination::from([(i, F::one()), (0, -s)])));
+        r1cs.add_constraint(R1CSConstraint::new_inverse_constraint(s, i, inverse_variable));
+    } else {
+        r1cs.add_constraint(R1CSConstraint::new_final_constraint(s, i));
+    }
+}
@@ -0,0 +1,121 @@
+//! This module implements the number theoretic transform (NTT), i.e. the fast Fourier transform
+//! over a finite field.
+//!
+//! Given a primitive root of unity `ω` of order `n = 2^k`, the NTT maps the coefficients
+//! `p_0, ..., p_{n-1}` of a polynomial `p` of degree less than `n` to its values `p(ω^0), ...,
+//! p(ω^{n-1})` at the powers of `ω`, and the inverse NTT maps the values back to the coefficients.
+//! Using the radix-2 Cooley-Tukey algorithm, both take `O(n log n)` field operations rather than
+//! the `O(n^2)` of evaluating at each point. Polynomials can thus be multiplied by evaluating both
+//! at enough points, multiplying the values, and interpolating the product (see
+//! `UnivariatePolynomial::mul_ntt`).
+//!
+//! The transform of length `2^k` needs a root of unity of that order, which exists when `2^k` divides
+//! `p - 1` (see `Field::root_of_unity`). The fields of the library support lengths up to `2^28` (BN254),
+//! `2^32` (BLS12-381 and Goldilocks) and `2^27` (BabyBear).
+
+use crate::field::Field;
+
+/// Replaces the coefficients `p_0, ..., p_{n-1}` of a polynomial `p` by its values `p(ω^0), ..., p(ω^{n-1})`,
+/// where `ω = F::root_of_unity(k)` for `n = 2^k`. An error is returned if the length is not a power of
+/// two, or if the field has no root of unity of that order.
+pub fn ntt<F: Field>(values: &mut [F]) -> Result<(), String> {
+    let root = root_of_unity(values.len())?;
+    transform(values, root);
+    Ok(())
+}
+
+/// Replaces the values `p(ω^0), ..., p(ω^{n-1})` of a polynomial `p` of degree less than `n` by its
+/// coefficients, i.e. the inverse of `ntt`. The transform uses the root `ω^{-1}` and divides by `n`.
+pub fn inverse_ntt<F: Field>(values: &mut [F]) -> Result<(), String> {
+    let root: F = root_of_unity(values.len())?;
+    transform(values, root.inverse().unwrap());
+    let scale = F::from(values.len() as u64).inverse().unwrap();
+    for value in values.iter_mut() {
+        *value = *value * scale;
+    }
+    Ok(())
+}
+
+/// Returns the root of unity of order `n` used by the transform of length `n`.
+fn root_of_unity<F: Field>(n: usize) -> Result<F, String> {
+    if !n.is_power_of_two() {
+        return Err(format!("The length {} of the transform is not a power of two", n));
+    }
+    F::root_of_unity(n.trailing_zeros()).ok_or(format!("The field has no root of unity of order {}", n))
+}
+
+/// Computes the values `Σ_j v_j root^{jk}` for `k < n` in place, where `root` has order `n = 2^k`.
+///
+/// The values are first put in bit-reversed order, after which each round combines pairs of
+/// transforms of length `m / 2` into transforms of length `m`, using the butterfly
+/// `(u, v) -> (u + ω^j v, u - ω^j v)` with the powers `ω^j` of a root of order `m`.
+fn transform<F: Field>(values: &mut [F], root: F) {
+    let n = values.len();
+    if n <= 1 {
+        return;
+    }
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    // The powers of the root of order n. A root of order m is root^(n / m), so its powers are every (n / m)th of these.
+    let twiddles: Vec<F> = std::iter::successors(Some(F::one()), |w| Some(*w * root)).take(n / 2).collect();
+    let mut m = 2;
+    while m <= n {
+        let stride = n / m;
+        for chunk in values.chunks_exact_mut(m) {
+            let (low, high) = chunk.split_at_mut(m / 2);
+            for (j, (u, v)) in low.iter_mut().zip(high.iter_mut()).enumerate() {
+                let t = twiddles[j * stride] * *v;
+                *v = *u - t;
+                *u = *u + t;
+            }
+        }
+        m *= 2;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{BabyBear, Bls12_381, Bn254, Goldilocks};
+    use crate::polynomial::UnivariatePolynomial;
+
+    /// Checks that the NTT of the coefficients of a polynomial gives its values at the powers of the root,
+    /// and that the inverse NTT gives back the coefficients.
+    fn check_ntt<F: Field>(n: usize) {
+        let coefficients: Vec<F> = (0..n as u64).map(|k| F::from(k * k + 3)).collect();
+        let polynomial = UnivariatePolynomial::new(coefficients.clone());
+        let root = F::root_of_unity(n.trailing_zeros()).unwrap();
+        let mut values = coefficients.clone();
+        ntt(&mut values).unwrap();
+        for (k, value) in values.iter().enumerate() {
+            assert_eq!(*value, polynomial.evaluate(root.pow(k as u64)));
+        }
+        inverse_ntt(&mut values).unwrap();
+        assert_eq!(values, coefficients);
+    }
+
+    #[test]
+    fn test_ntt() {
+        for n in [1, 2, 4, 16, 64] {
+            check_ntt::<Bn254>(n);
+            check_ntt::<Bls12_381>(n);
+            check_ntt::<Goldilocks>(n);
+            check_ntt::<BabyBear>(n);
+        }
+    }
+
+    #[test]
+    fn test_ntt_errors() {
+        let mut values = vec![Bn254::one(); 6];
+        assert!(ntt(&mut values).err().unwrap().contains("not a power of two"));
+        assert_eq!(values, vec![Bn254::one(); 6]);
+        let error = root_of_unity::<BabyBear>(1 << 28).err().unwrap();
+        assert_eq!(error, "The field has no root of unity of order 268435456");
+    }
+}
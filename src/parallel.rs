@@ -0,0 +1,39 @@
+//! This module implements the loops over constraints and variables which may run in parallel.
+//!
+//! With the feature `parallel`, the loops are split into chunks which are run on the thread pool
+//! of rayon. Without it, the same loops run sequentially, so that the rest of the library does
+//! not depend on whether the feature is enabled. In both cases, the results are identical: the
+//! values are returned in order, and searches return the first matching index.
+
+use std::ops::Range;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// The smallest number of indices handed to a thread at once. Evaluating a single constraint
+/// takes far less time than sending it to another thread, so the loops are only split into
+/// chunks of at least this many indices.
+#[cfg(feature = "parallel")]
+const MIN_CHUNK_SIZE: usize = 1024;
+
+/// Returns `f(i)` for each index `i` in `range`, in order.
+pub(crate) fn map<T, G>(range: Range<usize>, f: G) -> Vec<T>
+where T: Send, G: Fn(usize) -> T + Sync + Send
+{
+    #[cfg(feature = "parallel")]
+    let values = range.into_par_iter().with_min_len(MIN_CHUNK_SIZE).map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    let values = range.map(f).collect();
+    values
+}
+
+/// Returns the first index `i` in `range` for which `predicate(i)` holds, if any.
+pub(crate) fn position<G>(range: Range<usize>, predicate: G) -> Option<usize>
+where G: Fn(usize) -> bool + Sync + Send
+{
+    #[cfg(feature = "parallel")]
+    let position = range.into_par_iter().with_min_len(MIN_CHUNK_SIZE).find_first(|i| predicate(*i));
+    #[cfg(not(feature = "parallel"))]
+    let position = range.into_iter().find(|i| predicate(*i));
+    position
+}
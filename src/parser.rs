@@ -14,9 +14,14 @@
 use pest::Parser;
 use pest_derive::Parser;
 use std::collections::HashMap;
+use std::path::Path;
 
+use crate::field::Field;
+use crate::gadgets;
+use crate::linear_combination::LinearCombination;
+use crate::lowering;
 use crate::r1cs::R1CS;
-use crate::r1cs_constraint::{R1CSConstraint, R1CSSumConstraint};
+use crate::r1cs_constraint::R1CSConstraint;
 
 
 
@@ -26,240 +31,1065 @@ use crate::r1cs_constraint::{R1CSConstraint, R1CSSumConstraint};
 /// grammar defined in *poly.pest*.
 struct PolyParser;
 
+/// A user-defined function, given by the names of its parameters and the formula of its body.
+struct Function<'i> {
+    parameters: Vec<String>,
+    body: pest::iterators::Pair<'i, Rule>,
+}
+
+/// A call of a user-defined function which is currently being inlined, or an iteration
+/// of a sum or product which is currently being unrolled.
+/// 
+/// Each frame has a scope, distinguishing its nodes from identical nodes of other calls or
+/// iterations in the cache of visited nodes.
+enum Frame {
+    /// A call of `function`, with the variables given as arguments by the names of the parameters.
+    Call { function: String, arguments: HashMap<String, usize>, scope: usize },
+    /// An iteration of a sum or product, where the loop index `index` has the value `value`.
+    Iteration { index: String, value: i64, scope: usize },
+}
+
+impl Frame {
+    /// Returns the scope of the frame.
+    fn scope(&self) -> usize {
+        match self {
+            Frame::Call { scope, .. } | Frame::Iteration { scope, .. } => *scope,
+        }
+    }
+}
+
+/// The names of the builtin functions (see *poly.pest*), which cannot be used for user-defined functions.
+const BUILTINS: [&str; 5] = ["bool", "bits", "sum", "product", "dot"];
+
+/// Identifies a scope, i.e. the nodes of a function call or a loop iteration which may be shared.
+#[derive(PartialEq, Eq, Hash)]
+enum ScopeKey {
+    /// A call of a function with the given argument variables.
+    Call(String, Vec<usize>),
+    /// An iteration of a loop in the given parent scope, with the loop index set to the given value.
+    Iteration(usize, String, i64),
+}
+
+/// The length of the longest nodes which are identified by their text (see `NodeInterner`).
+const MAX_NODE_TEXT_LENGTH: usize = 64;
+
+/// A part of a node of the parse tree: either text (between its children, or its whole text if it
+/// is short), or one of its children, each given by its id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum NodePart {
+    Text(usize),
+    Child(usize),
+}
+
+/// Assigns ids to the nodes of a parse tree, such that two nodes have the same id exactly
+/// when they have the same rule and the same text.
+///
+/// Storing the text of every node would take space quadratic in the nesting depth, since the
+/// text of a node contains the text of all its descendants. Nodes of at most `MAX_NODE_TEXT_LENGTH`
+/// bytes, which make up most of a typical input, are still identified by their rule and their text.
+/// Longer nodes are identified by their rule, the ids of their children and the ids of the texts
+/// between them, so that each character of the input belongs to the parts of a single long node,
+/// and the ids of long nodes take linear time and space in total.
+#[derive(Default)]
+struct NodeInterner {
+    /// The ids of the distinct texts of short nodes, and of the texts between the children of long nodes.
+    texts: HashMap<Box<str>, usize>,
+    /// The ids of the distinct nodes consisting of a single part, i.e. of the short nodes and of the
+    /// long nodes with a single child spanning the whole node, by their rule and their part.
+    single_part_ids: HashMap<(Rule, NodePart), usize>,
+    /// The ids of the other distinct long nodes, by their rule and their parts.
+    ids: HashMap<(Rule, Box<[NodePart]>), usize>,
+    /// The ids of the long nodes with several parts which have already been interned, by their rule and
+    /// their position in the input. The ids of the other nodes are cheap to recompute, so their positions
+    /// are not stored.
+    positions: HashMap<(Rule, usize, usize), usize>,
+}
+
+impl NodeInterner {
+    /// Returns the id of a node, interning it and its descendants if needed.
+    fn id(&mut self, pair: &pest::iterators::Pair<Rule>) -> usize {
+        let span = pair.as_span();
+        let mut children = pair.clone().into_inner().peekable();
+        let single_part = match children.peek() {
+            _ if span.end() - span.start() <= MAX_NODE_TEXT_LENGTH => Some(NodePart::Text(self.text_id(span.as_str()))),
+            None => Some(NodePart::Text(self.text_id(span.as_str()))),
+            Some(child) if child.as_span() == span => Some(NodePart::Child(self.id(child))),
+            Some(_) => None,
+        };
+        if let Some(part) = single_part {
+            let next_id = self.num_ids();
+            return *self.single_part_ids.entry((pair.as_rule(), part)).or_insert(next_id);
+        }
+        let position = (pair.as_rule(), span.start(), span.end());
+        if let Some(id) = self.positions.get(&position) {
+            return *id;
+        }
+        let input = pair.get_input();
+        let mut parts = Vec::new();
+        let mut end = span.start();
+        for child in children {
+            let child_span = child.as_span();
+            if child_span.start() > end {
+                parts.push(NodePart::Text(self.text_id(&input[end..child_span.start()])));
+            }
+            parts.push(NodePart::Child(self.id(&child)));
+            end = child_span.end();
+        }
+        if span.end() > end {
+            parts.push(NodePart::Text(self.text_id(&input[end..span.end()])));
+        }
+        let next_id = self.num_ids();
+        let id = *self.ids.entry((pair.as_rule(), parts.into())).or_insert(next_id);
+        self.positions.insert(position, id);
+        id
+    }
+
+    /// Returns the number of distinct nodes interned so far.
+    fn num_ids(&self) -> usize {
+        self.single_part_ids.len() + self.ids.len()
+    }
+
+    /// Returns the id of a text between the children of a node, interning it if needed.
+    fn text_id(&mut self, text: &str) -> usize {
+        if let Some(id) = self.texts.get(text) {
+            return *id;
+        }
+        let id = self.texts.len();
+        self.texts.insert(text.into(), id);
+        id
+    }
+}
+
 /// A visitor which visits the nodes of the parse tree generated by the
 /// `PolyParser` and creates new matching constraints for an `R1CS`.
-struct ParseTreeVisitor {
-    visited_nodes: HashMap<(Rule, String), usize>, 
-    r1cs: R1CS,
+struct ParseTreeVisitor<'i, F: Field> {
+    /// The variables of the visited nodes, by their id (see `NodeInterner`) and the scope they were visited in.
+    /// The top level scope is 0, while the bodies of function calls and loop iterations get their own scopes.
+    visited_nodes: HashMap<(usize, usize), usize>,
+    /// The ids of the nodes of the parse tree, which is only ever taken from a single input.
+    nodes: NodeInterner,
+    /// The number of bits which the value of a variable is known to fit in, for the variables
+    /// where this is known (e.g. variables restricted by the builtin `bits`).
+    bit_widths: HashMap<usize, usize>,
+    /// The values of the variables created for constant terms.
+    constants: HashMap<usize, F>,
+    /// The variables bound to names by let-bindings.
+    bindings: HashMap<String, usize>,
+    /// The lengths of the vectors bound to names by let-bindings.
+    vector_lengths: HashMap<String, usize>,
+    /// The user-defined functions, by their names.
+    functions: HashMap<String, Function<'i>>,
+    /// The function calls and loop iterations currently being visited, with the innermost one last.
+    frames: Vec<Frame>,
+    /// The scopes of the function calls and loop iterations. Calls of the same function with the same
+    /// arguments share scope (and so do identical iterations), so that their nodes are shared.
+    scopes: HashMap<ScopeKey, usize>,
+    r1cs: R1CS<F>,
 }
 
-impl ParseTreeVisitor {
+impl<'i, F: Field> ParseTreeVisitor<'i, F> {
     /// Creates a new `ParseTreeVisitor` instance.
     fn new() -> Self {
+        Self::with_r1cs(R1CS::new())
+    }
+
+    /// Creates a new `ParseTreeVisitor` instance, which adds constraints to an existing R1CS system.
+    fn with_r1cs(r1cs: R1CS<F>) -> Self {
         ParseTreeVisitor {
             visited_nodes: HashMap::new(),
-            r1cs: R1CS::new(),
+            nodes: NodeInterner::default(),
+            bit_widths: HashMap::new(),
+            constants: HashMap::new(),
+            bindings: HashMap::new(),
+            vector_lengths: HashMap::new(),
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            scopes: HashMap::new(),
+            r1cs,
         }
     }
 
     /// A wrapper function which checks if an identical node has already been visited.
     /// In this case, no new constraints should be created, since this sub-polynomial
     /// has already been given a variable. Instead this variable is returned.
-    fn cache_wrapper<F>(&mut self, f: F, pair: pest::iterators::Pair<Rule>) -> usize
-    where F: for<'a> Fn(&'a mut Self, pest::iterators::Pair<Rule>) -> usize
+    /// 
+    /// Nodes are only identical if they were visited in the same scope, since e.g. the
+    /// parameter `x` of a function is a different variable in each call of the function.
+    fn cache_wrapper<G>(&mut self, f: G, pair: pest::iterators::Pair<Rule>) -> Result<usize, String>
+    where G: for<'a> Fn(&'a mut Self, pest::iterators::Pair<Rule>) -> Result<usize, String>
     {
-        let key = (pair.as_rule(), pair.as_str().to_string());
-        if self.visited_nodes.contains_key(&key) {
-            self.visited_nodes[&key]
+        let key = (self.nodes.id(&pair), self.current_scope());
+        if let Some(variable) = self.visited_nodes.get(&key) {
+            Ok(*variable)
         } else {
-            let variable = f(self, pair);
+            let variable = f(self, pair)?;
             self.visited_nodes.insert(key, variable);
-            variable
+            Ok(variable)
         }
     }
 
-    /// Visits a variable node in the parse tree and adds it to the R1CS as input.
-    fn visit_variable(&mut self, variable_pair: pest::iterators::Pair<Rule>) -> usize {
+    /// Returns the scope of the innermost frame, or 0 at the top level.
+    fn current_scope(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.scope())
+    }
+
+    /// Enters a new frame in the scope identified by `key`, which is shared by all frames with the same key.
+    fn push_frame(&mut self, key: ScopeKey, frame: impl FnOnce(usize) -> Frame) {
+        let next_scope = self.scopes.len() + 1;
+        let scope = *self.scopes.entry(key).or_insert(next_scope);
+        self.frames.push(frame(scope));
+    }
+
+    /// Returns the value of the loop index `name`, if it is the index of a loop being unrolled.
+    /// Loop indices are not visible inside the bodies of functions called in the loop.
+    fn loop_index_value(&self, name: &str) -> Option<i64> {
+        for frame in self.frames.iter().rev() {
+            match frame {
+                Frame::Iteration { index, value, .. } if index == name => return Some(*value),
+                Frame::Iteration { .. } => {}
+                Frame::Call { .. } => return None,
+            }
+        }
+        None
+    }
+
+    /// Returns the variable with the given name. If the name has been bound by a let-binding,
+    /// the bound variable is returned. Otherwise, the name is an input variable, which is added
+    /// to the R1CS unless it already exists (e.g. since it was used in another loop iteration).
+    /// The name of a user-defined function cannot be used as an input variable.
+    /// Inside the body of a function, the variable must be one of its parameters, and the
+    /// variable given as argument is returned.
+    fn resolve_variable(&mut self, name: &str) -> Result<usize, String> {
+        let call = self.frames.iter().rev().find_map(|frame| match frame {
+            Frame::Call { function, arguments, .. } => Some((function, arguments)),
+            Frame::Iteration { .. } => None,
+        });
+        if let Some((function, arguments)) = call {
+            return arguments.get(name).copied()
+                .ok_or(format!("The variable `{}` is not a parameter of the function `{}`.", name, function));
+        }
+        if let Some(variable) = self.bindings.get(name).or_else(|| self.r1cs.variables().get(name)) {
+            return Ok(*variable);
+        }
+        if self.functions.contains_key(name) {
+            return Err(format!("The function `{}` cannot be used as an input variable.", name));
+        }
+        if let Some(length) = self.vector_lengths.get(name) {
+            return Err(format!("The name `{}` is bound to a vector of length {}, so it cannot be used as a scalar. Use an element such as `{}[0]`, or `{}[..]` for the whole vector.", name, length, name, name));
+        }
+        Ok(self.r1cs.add_input_variable(name.to_string()))
+    }
+
+    /// Returns the variable of the element `name[index]` (see `resolve_variable`). If `name` is bound to a
+    /// vector by a let-binding, an error is returned unless the index is smaller than its length.
+    fn resolve_element(&mut self, name: &str, index: i64) -> Result<usize, String> {
+        if let Some(length) = self.vector_lengths.get(name) {
+            if index >= *length as i64 {
+                return Err(format!("The index {} is out of bounds for the vector `{}` of length {}.", index, name, length));
+            }
+        }
+        self.resolve_variable(&format!("{}[{}]", name, index))
+    }
+
+    /// Visits a variable node in the parse tree, returning its variable (see `resolve_variable`).
+    /// Inside a sum or product, the loop index is instead a constant, given by the current iteration.
+    fn visit_variable(&mut self, variable_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
-            s.r1cs.add_input_variable(input_pair.as_str().to_string())
+            let name = input_pair.as_str();
+            match s.loop_index_value(name) {
+                Some(value) => Ok(s.add_constant(F::from(value))),
+                None => s.resolve_variable(name),
+            }
         } , variable_pair)
     }
 
+    /// Visits an indexed variable node `x[i]` in the parse tree. The index is evaluated, and the
+    /// variable is named by its value, so that e.g. `x[2*i]` for `i = 1` is the variable `x[2]`.
+    fn visit_indexed_variable(&mut self, indexed_variable_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut name = String::new();
+            let mut index = 0;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::variable => {
+                        name = pair.as_str().to_string();
+                    }
+                    Rule::index => {
+                        index = s.evaluate_index(pair)?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_indexed_variable. Only variable and index rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            if index < 0 {
+                return Err(format!("The index of `{}[{}]` is negative.", name, index));
+            }
+            s.resolve_element(&name, index)
+        }, indexed_variable_pair)
+    }
+
+    /// Evaluates an index node in the parse tree, i.e. an integer expression of numbers and loop indices.
+    fn evaluate_index(&self, index_pair: pest::iterators::Pair<Rule>) -> Result<i64, String> {
+        let text = index_pair.as_str().trim().to_string();
+        let overflow = || format!("The index `{}` is too large.", text);
+        let mut value: i64 = 0;
+        let mut sign = 1;
+        for pair in index_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::index_plus => sign = 1,
+                Rule::index_minus => sign = -1,
+                Rule::index_product => {
+                    let mut product: i64 = 1;
+                    for factor in pair.into_inner() {
+                        let factor = match factor.as_rule() {
+                            Rule::positive_number => parse_small_number(factor.as_str(), "an index")?,
+                            Rule::variable => self.loop_index_value(factor.as_str())
+                                .ok_or(format!("The variable `{}` in the index `{}` is not a loop index.", factor.as_str(), text))?,
+                            Rule::index => self.evaluate_index(factor)?,
+                            _ => {
+                                panic!("Rule {:?} found in evaluate_index. Only positive_number, variable and index rules are expected there.", factor.as_rule());
+                            }
+                        };
+                        product = product.checked_mul(factor).ok_or_else(overflow)?;
+                    }
+                    value = value.checked_add(sign * product).ok_or_else(overflow)?;
+                }
+                _ => {
+                    panic!("Rule {:?} found in evaluate_index. Only index_product and sign rules are expected there.", pair.as_rule());
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    /// Evaluates an exponent node in the parse tree, which is either a number or an index such as `i + 1`.
+    fn evaluate_exponent(&self, exponent_pair: pest::iterators::Pair<Rule>) -> Result<u32, String> {
+        let text = exponent_pair.as_str().trim().to_string();
+        let pair = exponent_pair.into_inner().next().expect("Parsed an empty exponent.");
+        let value = match pair.as_rule() {
+            Rule::positive_number => return parse_small_number(pair.as_str(), "an exponent"),
+            Rule::variable => self.loop_index_value(pair.as_str())
+                .ok_or(format!("The exponent `{}` is neither a number nor a loop index.", text))?,
+            Rule::index => self.evaluate_index(pair)?,
+            _ => {
+                panic!("Rule {:?} found in evaluate_exponent. Only positive_number, variable and index rules are expected there.", pair.as_rule());
+            }
+        };
+        u32::try_from(value).ok()
+            .ok_or(format!("The exponent `{}` = {} is negative or too large.", text, value))
+    }
+
+    /// Visits a sum or product node, such as `sum(i in 0..8, c[i] * x[i])`, in the parse tree. The loop is
+    /// unrolled, visiting the body once for each value of the index in the range (excluding its end).
+    /// The variables of the iterations are then summed or multiplied, just as for an expression or a product.
+    /// 
+    /// Each iteration gets its own scope, since e.g. `x[i]` is a different variable in each iteration.
+    fn visit_loop(&mut self, loop_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let is_sum = input_pair.as_rule() == Rule::sum_loop;
+            let mut index = String::new();
+            let mut range = (0, 0);
+            let mut body = None;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::variable => {
+                        index = pair.as_str().to_string();
+                    }
+                    Rule::range => {
+                        let mut bounds = pair.into_inner();
+                        let start = s.evaluate_index(bounds.next().expect("Parsed a range without a start."))?;
+                        let end = s.evaluate_index(bounds.next().expect("Parsed a range without an end."))?;
+                        range = (start, end);
+                    }
+                    Rule::formula => {
+                        body = Some(pair);
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_loop. Only variable, range and formula rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            let body = body.expect("Parsed a loop without a body.");
+            let mut variable_positions = Vec::new();
+            for value in range.0..range.1 {
+                let key = ScopeKey::Iteration(s.current_scope(), index.clone(), value);
+                s.push_frame(key, |scope| Frame::Iteration { index: index.clone(), value, scope });
+                let result = s.visit_formula(body.clone());
+                s.frames.pop();
+                variable_positions.push(result?);
+            }
+            if is_sum {
+                Ok(s.create_sum_constraint(&variable_positions))
+            } else {
+                Ok(lowering::product(&mut s.r1cs, &variable_positions))
+            }
+        }, loop_pair)
+    }
+
+    /// Visits a vector expression node in the parse tree, and returns the variables of its elements.
+    /// Vectors are combined elementwise, creating one constraint per element.
+    fn visit_vector_expression(&mut self, vector_pair: pest::iterators::Pair<Rule>) -> Result<Vec<usize>, String> {
+        let text = vector_pair.as_str().trim();
+        let rule = vector_pair.as_rule();
+        let mut result: Option<Vec<usize>> = None;
+        let mut is_subtraction = false;
+        for pair in vector_pair.into_inner() {
+            let operand = match pair.as_rule() {
+                Rule::vector_add | Rule::vector_sub => {
+                    is_subtraction = pair.as_rule() == Rule::vector_sub;
+                    continue;
+                }
+                Rule::vector_list => {
+                    let mut elements = Vec::new();
+                    for element in pair.into_inner() {
+                        elements.push(self.visit_formula(element)?);
+                    }
+                    elements
+                }
+                Rule::vector_slice => self.visit_vector_slice(pair)?,
+                Rule::vector_expression | Rule::elementwise_product => self.visit_vector_expression(pair)?,
+                _ => {
+                    panic!("Rule {:?} found in visit_vector_expression. Only vector rules are expected there.", pair.as_rule());
+                }
+            };
+            result = Some(match result {
+                None => operand,
+                Some(current) => {
+                    if current.len() != operand.len() {
+                        return Err(format!("Cannot combine vectors of lengths {} and {} in `{}`.", current.len(), operand.len(), text));
+                    }
+                    current.iter().zip(operand.iter()).map(|(i, j)| {
+                        if rule == Rule::elementwise_product {
+                            lowering::product(&mut self.r1cs, &[*i, *j])
+                        } else {
+                            let mut sum = LinearCombination::new();
+                            sum.add_term(*i, F::one());
+                            if is_subtraction {
+                                sum.add_term(*j, -F::one());
+                            } else {
+                                sum.add_term(*j, F::one());
+                            }
+                            lowering::linear_combination(&mut self.r1cs, sum)
+                        }
+                    }).collect()
+                }
+            });
+        }
+        Ok(result.expect("Parsed an empty vector expression."))
+    }
+
+    /// Visits a vector slice node `x[i..j]` in the parse tree, and returns the variables `x[i]`, ..., `x[j - 1]`.
+    /// The slice `v[..]` is the whole vector `v`, which must be bound by a let-binding.
+    fn visit_vector_slice(&mut self, slice_pair: pest::iterators::Pair<Rule>) -> Result<Vec<usize>, String> {
+        let mut name = String::new();
+        let mut range = (0, 0);
+        for pair in slice_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::variable => {
+                    name = pair.as_str().to_string();
+                }
+                Rule::range => {
+                    let mut bounds = pair.into_inner();
+                    let start = self.evaluate_index(bounds.next().expect("Parsed a range without a start."))?;
+                    let end = self.evaluate_index(bounds.next().expect("Parsed a range without an end."))?;
+                    if start < 0 {
+                        return Err(format!("The slice of `{}` starts at the negative index {}.", name, start));
+                    }
+                    range = (start, end);
+                }
+                Rule::full_range => {
+                    let length = self.vector_lengths.get(&name)
+                        .ok_or(format!("The length of `{}` is unknown, since it is not bound to a vector. Use a range such as `{}[0..4]`.", name, name))?;
+                    range = (0, *length as i64);
+                }
+                _ => {
+                    panic!("Rule {:?} found in visit_vector_slice. Only variable, range and full_range rules are expected there.", pair.as_rule());
+                }
+            }
+        }
+        (range.0..range.1).map(|index| self.resolve_element(&name, index)).collect()
+    }
+
+    /// Visits a builtin function node `dot(a, b)` in the parse tree, creating one multiplication
+    /// constraint per element of the vectors `a` and `b`, and a single constraint for the sum of the products.
+    fn visit_dot_call(&mut self, dot_call_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let text = input_pair.as_str().trim();
+            let mut vectors = Vec::new();
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::vector_expression => {
+                        vectors.push(s.visit_vector_expression(pair)?);
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_dot_call. Only vector_expression rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            if vectors[0].len() != vectors[1].len() {
+                return Err(format!("Cannot compute the dot product of vectors of lengths {} and {} in `{}`.", vectors[0].len(), vectors[1].len(), text));
+            }
+            let products: Vec<usize> = vectors[0].iter().zip(vectors[1].iter())
+                .map(|(i, j)| lowering::product(&mut s.r1cs, &[*i, *j]))
+                .collect();
+            Ok(s.create_sum_constraint(&products))
+        }, dot_call_pair)
+    }
+
+    /// Creates a new variable for the constant `s`, with the constraint `1 * s = u_k`, and returns its index.
+    fn add_constant(&mut self, s: F) -> usize {
+        let variable_position = lowering::constant(&mut self.r1cs, s);
+        self.restrict_bit_width(variable_position, s.bit_length());
+        self.constants.insert(variable_position, s);
+        variable_position
+    }
+
+    /// Creates a variable for the sum of the given variables, and returns its index. If there is only
+    /// one variable, no new constraint is needed, and the variable itself is returned.
+    fn create_sum_constraint(&mut self, variable_positions: &[usize]) -> usize {
+        match variable_positions {
+            [] => self.add_constant(F::zero()),
+            [variable_position] => *variable_position,
+            _ => {
+                let mut sum = LinearCombination::new();
+                for variable_position in variable_positions {
+                    sum.add_term(*variable_position, F::one());
+                }
+                lowering::linear_combination(&mut self.r1cs, sum)
+            }
+        }
+    }
+
     /// Visits a variable exponentiation node in the parse tree and creates the corresponding
     /// constraints in the R1CS. 
-    fn visit_varpow(&mut self, varpow_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_varpow(&mut self, varpow_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
             let mut exponent = 1;
             let mut variable_position = 0;
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
                     Rule::variable => {
-                        variable_position = s.visit_variable(pair);
+                        variable_position = s.visit_variable(pair)?;
+                    }
+                    Rule::indexed_variable => {
+                        variable_position = s.visit_indexed_variable(pair)?;
                     }
                     Rule::exponent => {
-                        exponent = pair.as_str().parse().expect("Not a number");
+                        exponent = s.evaluate_exponent(pair)?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_varpow. Only variable, indexed_variable and exponent rules are expected there.", pair.as_rule());
+                    }
+                }
+            }            
+            Ok(lowering::power(&mut s.r1cs, variable_position, exponent))
+        }, varpow_pair)
+    }
+
+    /// Visits a builtin function node `bool(p)` in the parse tree, and constrains the variable of `p`
+    /// to be boolean. The variable of `p` is returned.
+    fn visit_bool_call(&mut self, bool_call_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut variable_position = 0;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::expression => {
+                        variable_position = s.visit_expression(pair)?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_bool_call. Only expression rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            gadgets::assert_boolean(&mut s.r1cs, variable_position);
+            s.restrict_bit_width(variable_position, 1);
+            Ok(variable_position)
+        }, bool_call_pair)
+    }
+
+    /// Visits a builtin function node `bits(p, n)` in the parse tree, and decomposes the variable of `p`
+    /// into `n` bits. This restricts `p` to values fitting in `n` bits. The variable of `p` is returned.
+    /// An error is returned if `n` is not smaller than the number of bits of the modulus, since the
+    /// decomposition would then not be unique.
+    fn visit_bits_call(&mut self, bits_call_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let call = input_pair.as_str();
+            let mut variable_position = 0;
+            let mut size = 0;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::expression => {
+                        variable_position = s.visit_expression(pair)?;
+                    }
+                    Rule::positive_number => {
+                        size = parse_small_number(pair.as_str(), "a number of bits")?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_bits_call. Only expression and positive_number rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            if size >= F::modulus_bit_length() {
+                return Err(format!("Cannot compile `{}`, since {} bits do not restrict the values of a field with a {} bit modulus.", call, size, F::modulus_bit_length()));
+            }
+            gadgets::to_bits(&mut s.r1cs, variable_position, size);
+            s.restrict_bit_width(variable_position, size);
+            Ok(variable_position)
+        }, bits_call_pair)
+    }
+
+    /// Visits a call node `f(p_1, ..., p_n)` in the parse tree. If `f` is a user-defined function,
+    /// the call is inlined, creating the constraints of the body of `f` with its parameters replaced
+    /// by the variables of the arguments. Otherwise, `f(p)` is the product of the variable `f` and `p`.
+    /// In both cases, an exponent applies to the call (respectively to `p`) as for a parenth.
+    fn visit_call(&mut self, call_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut name_pair = None;
+            let mut arguments = Vec::new();
+            let mut exponent = 1;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::variable => {
+                        name_pair = Some(pair);
+                    }
+                    Rule::formula => {
+                        arguments.push(s.visit_formula(pair)?);
+                    }
+                    Rule::exponent => {
+                        exponent = s.evaluate_exponent(pair)?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_call. Only variable, formula and exponent rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            let name_pair = name_pair.expect("Parsed a call without a name.");
+            let name = name_pair.as_str().to_string();
+            if s.functions.contains_key(&name) {
+                let result = s.inline_function(&name, arguments)?;
+                Ok(lowering::power(&mut s.r1cs, result, exponent))
+            } else if arguments.len() == 1 {
+                let variable_position = s.visit_variable(name_pair)?;
+                let argument = lowering::power(&mut s.r1cs, arguments[0], exponent);
+                Ok(lowering::product(&mut s.r1cs, &[variable_position, argument]))
+            } else {
+                Err(format!("Call of the undefined function `{}`.", name))
+            }
+        }, call_pair)
+    }
+
+    /// Inlines a call of the user-defined function `name` with the given argument variables,
+    /// returning the variable of its body.
+    /// 
+    /// The body is visited in a scope given by the function and its arguments, so that calls with
+    /// different arguments get fresh variables, while calls with identical arguments share them.
+    /// Recursive calls are rejected, since inlining them would never terminate.
+    fn inline_function(&mut self, name: &str, arguments: Vec<usize>) -> Result<usize, String> {
+        if self.frames.iter().any(|frame| matches!(frame, Frame::Call { function, .. } if function == name)) {
+            return Err(format!("The function `{}` is called recursively, which is not supported.", name));
+        }
+        let function = &self.functions[name];
+        if function.parameters.len() != arguments.len() {
+            return Err(format!("The function `{}` takes {} arguments, but {} were given.", name, function.parameters.len(), arguments.len()));
+        }
+        let arguments_by_name = function.parameters.iter().cloned().zip(arguments.iter().copied()).collect();
+        let body = function.body.clone();
+        self.push_frame(ScopeKey::Call(name.to_string(), arguments), |scope| Frame::Call { function: name.to_string(), arguments: arguments_by_name, scope });
+        let result = self.visit_formula(body);
+        self.frames.pop();
+        result
+    }
+
+    /// Records that the value of the variable `u_i` is known to fit in `size` bits.
+    fn restrict_bit_width(&mut self, i: usize, size: usize) {
+        let width = self.bit_widths.entry(i).or_insert(size);
+        *width = size.min(*width);
+    }
+
+    /// Visits a comparison node in the parse tree, and creates a new boolean variable which is
+    /// `1` exactly when the comparison holds. If the node consists of a single expression without
+    /// any comparator, the variable of the expression is returned instead.
+    /// 
+    /// The comparison is lowered using the gadgets `less_than` and `less_or_equal`, which need to know
+    /// the number of bits that the compared values fit in. Hence, both sides of the comparison must
+    /// be known to fit in a limited number of bits, or an error is returned.
+    fn visit_comparison(&mut self, comparison_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let comparison = input_pair.as_str();
+            let mut operands = Vec::new();
+            let mut comparator = Rule::less_than;
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::expression => {
+                        operands.push(s.visit_expression(pair)?);
+                    }
+                    Rule::less_than | Rule::less_or_equal | Rule::greater_than | Rule::greater_or_equal => {
+                        comparator = pair.as_rule();
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_comparison. Only expression and comparator rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            if operands.len() == 1 {
+                return Ok(operands[0]);
+            }
+            let (left, right) = (operands[0], operands[1]);
+            let mut size = 0;
+            for operand in [left, right] {
+                match s.bit_widths.get(&operand) {
+                    Some(width) => size = size.max(*width),
+                    None => return Err(format!("Cannot compile the comparison `{}`, since both sides must be known to fit in a limited number of bits. Use the builtin `bits` to restrict them.", comparison)),
+                }
+            }
+            if size + 1 >= F::modulus_bit_length() {
+                return Err(format!("Cannot compile the comparison `{}`, since values with {} bits are too large to compare.", comparison, size));
+            }
+            let comparison_variable = match comparator {
+                Rule::less_than => gadgets::less_than(&mut s.r1cs, left, right, size),
+                Rule::less_or_equal => gadgets::less_or_equal(&mut s.r1cs, left, right, size),
+                Rule::greater_than => gadgets::less_than(&mut s.r1cs, right, left, size),
+                _ => gadgets::less_or_equal(&mut s.r1cs, right, left, size),
+            };
+            s.restrict_bit_width(comparison_variable, 1);
+            Ok(comparison_variable)
+        }, comparison_pair)
+    }
+
+    /// Makes sure that the variable `u_i`, which is the variable of the operand `operand` of a
+    /// boolean operator, is boolean. If `u_i` is already known to be boolean, nothing is done.
+    /// If it is a constant which is not `0` or `1`, an error is returned. Otherwise, a constraint
+    /// restricting `u_i` to be boolean is added.
+    fn ensure_boolean(&mut self, i: usize, operand: &str) -> Result<(), String> {
+        if self.constants.get(&i).is_some_and(|constant| constant.bit_length() > 1) {
+            return Err(format!("The operand `{}` of a boolean operator is a constant which is neither 0 nor 1.", operand.trim()));
+        }
+        if self.bit_widths.get(&i).is_none_or(|width| *width > 1) {
+            gadgets::assert_boolean(&mut self.r1cs, i);
+            self.restrict_bit_width(i, 1);
+        }
+        Ok(())
+    }
+
+    /// Combines the variables of the operands of a boolean operator, using the gadget `operator`.
+    /// The operands are given as pairs of variables and their source text (used in error messages).
+    /// If there is only one operand, it is returned without any new constraints.
+    fn combine_boolean_operands<G>(&mut self, operands: Vec<(usize, &str)>, operator: G) -> Result<usize, String>
+    where G: Fn(&mut R1CS<F>, usize, usize) -> usize
+    {
+        if operands.len() == 1 {
+            return Ok(operands[0].0);
+        }
+        for (variable, operand) in operands.iter() {
+            self.ensure_boolean(*variable, operand)?;
+        }
+        let mut current_variable = operands[0].0;
+        for (variable, _) in operands.iter().skip(1) {
+            current_variable = operator(&mut self.r1cs, current_variable, *variable);
+        }
+        self.restrict_bit_width(current_variable, 1);
+        Ok(current_variable)
+    }
+
+    /// Visits a negation node `not b` in the parse tree, and creates the corresponding constraints in the R1CS.
+    fn visit_negation(&mut self, negation_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut variable_position = 0;
+            let mut operand = "";
+            for pair in input_pair.into_inner() {
+                operand = pair.as_str();
+                match pair.as_rule() {
+                    Rule::negation => {
+                        variable_position = s.visit_negation(pair)?;
+                    }
+                    Rule::comparison => {
+                        variable_position = s.visit_comparison(pair)?;
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_negation. Only negation and comparison rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            s.ensure_boolean(variable_position, operand)?;
+            let negation_variable = gadgets::boolean_not(&mut s.r1cs, variable_position);
+            s.restrict_bit_width(negation_variable, 1);
+            Ok(negation_variable)
+        }, negation_pair)
+    }
+
+    /// Visits a conjunction node `b_1 and b_2 and ...` in the parse tree, and creates the corresponding
+    /// constraints in the R1CS.
+    fn visit_conjunction(&mut self, conjunction_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut operands = Vec::new();
+            for pair in input_pair.into_inner() {
+                let operand = pair.as_str();
+                match pair.as_rule() {
+                    Rule::negation => {
+                        operands.push((s.visit_negation(pair)?, operand));
+                    }
+                    Rule::comparison => {
+                        operands.push((s.visit_comparison(pair)?, operand));
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_conjunction. Only negation and comparison rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            s.combine_boolean_operands(operands, gadgets::boolean_and)
+        }, conjunction_pair)
+    }
+
+    /// Visits an exclusive disjunction node `b_1 xor b_2 xor ...` in the parse tree, and creates the
+    /// corresponding constraints in the R1CS.
+    fn visit_exclusive_disjunction(&mut self, exclusive_disjunction_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut operands = Vec::new();
+            for pair in input_pair.into_inner() {
+                let operand = pair.as_str();
+                match pair.as_rule() {
+                    Rule::conjunction => {
+                        operands.push((s.visit_conjunction(pair)?, operand));
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_exclusive_disjunction. Only conjunction rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            s.combine_boolean_operands(operands, gadgets::boolean_xor)
+        }, exclusive_disjunction_pair)
+    }
+
+    /// Visits a disjunction node `b_1 or b_2 or ...` in the parse tree, and creates the corresponding
+    /// constraints in the R1CS.
+    fn visit_disjunction(&mut self, disjunction_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut operands = Vec::new();
+            for pair in input_pair.into_inner() {
+                let operand = pair.as_str();
+                match pair.as_rule() {
+                    Rule::exclusive_disjunction => {
+                        operands.push((s.visit_exclusive_disjunction(pair)?, operand));
+                    }
+                    _ => {
+                        panic!("Rule {:?} found in visit_disjunction. Only exclusive_disjunction rules are expected there.", pair.as_rule());
+                    }
+                }
+            }
+            s.combine_boolean_operands(operands, gadgets::boolean_or)
+        }, disjunction_pair)
+    }
+
+    /// Visits a conditional node `if b then p else q` in the parse tree, and creates the corresponding
+    /// constraints in the R1CS. The condition `b` is constrained to be boolean, and the conditional is
+    /// lowered to `b * (p - q) + q` using the gadget `select`.
+    fn visit_conditional(&mut self, conditional_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        self.cache_wrapper(|s, input_pair| {
+            let mut condition = (0, "");
+            let mut branches = Vec::new();
+            for pair in input_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::disjunction => {
+                        let operand = pair.as_str();
+                        condition = (s.visit_disjunction(pair)?, operand);
+                    }
+                    Rule::formula => {
+                        branches.push(s.visit_formula(pair)?);
                     }
                     _ => {
-                        panic!("Rule {:?} found in visit_varpow. Only variable and exponent rules are expected there.", pair.as_rule());
+                        panic!("Rule {:?} found in visit_conditional. Only disjunction and formula rules are expected there.", pair.as_rule());
                     }
                 }
-            }            
-            s.create_exponentiation_constraints(exponent, variable_position)
-        }, varpow_pair)
+            }
+            s.ensure_boolean(condition.0, condition.1)?;
+            let (then_variable, else_variable) = (branches[0], branches[1]);
+            let conditional_variable = gadgets::select(&mut s.r1cs, condition.0, then_variable, else_variable);
+            if let (Some(then_width), Some(else_width)) = (s.bit_widths.get(&then_variable), s.bit_widths.get(&else_variable)) {
+                let width = *then_width.max(else_width);
+                s.restrict_bit_width(conditional_variable, width);
+            }
+            Ok(conditional_variable)
+        }, conditional_pair)
     }
 
-    /// Creates constraints from exponentiations.
+    /// Visits a formula node in the parse tree. This function does not create any new constraints,
+    /// but instead returns the variable corresponding to the node that the formula consists of
+    /// (a conditional or a disjunction).
     /// 
-    /// Note that for any exponentiation where the power is larger than 2, we need
-    /// to create multiple constraints. We do this using binary exponentiation, in order
-    /// to keep the number of constraints low.
-    /// 
-    /// Constraints created by this function are of the form `u_i * u_j = u_k`,
-    /// where `u_i` and `u_j` are powers of the variable and `u_k` is a new power.
-    /// 
-    /// **Note**: This function ignores checking whether there is already a varible for
-    /// any of the intermediate powers. This is a potential optimization (if optimizing for
-    /// a minimal number of variables; the effect on performace would depend on the input
-    /// polynomial), but has not been implemented yet.
-    fn create_exponentiation_constraints(&mut self, mut exponent: i32, variable_position: usize) -> usize {
-        if exponent == 1 {
-            variable_position
-        } else {
-            let max_exponent_variable = self.r1cs.add_variable();
-            let mut current_exponent_variable = max_exponent_variable;
-            while exponent > 1 {
-                if exponent % 2 == 0 {
-                    exponent /= 2;
-                    if exponent == 1 {
-                        self.r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(variable_position, variable_position, current_exponent_variable));
-                    } else {
-                        let new_exponent_variable = self.r1cs.add_variable();
-                        self.r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(new_exponent_variable, new_exponent_variable, current_exponent_variable));
-                        current_exponent_variable = new_exponent_variable;
-                    }
-                } else {
-                    let new_exponent_variable = self.r1cs.add_variable();
-                    self.r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(variable_position, new_exponent_variable, current_exponent_variable));
-                    exponent -= 1;
-                    current_exponent_variable = new_exponent_variable;
+    /// In particular, this means that the function does not need the wrapper function `cache_wrapper`.
+    fn visit_formula(&mut self, formula_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        let mut variable_position = 0;
+        for pair in formula_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::conditional => {
+                    variable_position = self.visit_conditional(pair)?;
+                }
+                Rule::disjunction => {
+                    variable_position = self.visit_disjunction(pair)?;
+                }
+                _ => {
+                    panic!("Rule {:?} found in visit_formula. Only conditional and disjunction rules are expected there.", pair.as_rule());
                 }
             }
-            max_exponent_variable
         }
+        Ok(variable_position)
     }
-    
+
     /// Visits a factor node in the parse tree. This function does not create any new constraints,
     /// but instead returns the variable corresponding to the node that the factor consists of
-    /// (a varpow or a parenth).
+    /// (a builtin, a call, a varpow or a parenth).
     /// 
     /// In particular, this means that the function does not need the wrapper function `cache_wrapper`.
-    fn visit_factor(&mut self, factor_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_factor(&mut self, factor_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         let mut variable_position = 0;
         for pair in factor_pair.into_inner() {
             match pair.as_rule() {
                 Rule::varpow => {
-                    variable_position = self.visit_varpow(pair);
+                    variable_position = self.visit_varpow(pair)?;
                 }
                 Rule::parenth => {
-                    variable_position = self.visit_parenth(pair);
+                    variable_position = self.visit_parenth(pair)?;
+                }
+                Rule::bool_call => {
+                    variable_position = self.visit_bool_call(pair)?;
+                }
+                Rule::bits_call => {
+                    variable_position = self.visit_bits_call(pair)?;
+                }
+                Rule::call => {
+                    variable_position = self.visit_call(pair)?;
+                }
+                Rule::sum_loop | Rule::product_loop => {
+                    variable_position = self.visit_loop(pair)?;
+                }
+                Rule::dot_call => {
+                    variable_position = self.visit_dot_call(pair)?;
                 }
                 _ => {
-                    panic!("Rule {:?} found in visit_factor. Only varpow and parenth rules are expected there.", pair.as_rule());
+                    panic!("Rule {:?} found in visit_factor. Only varpow, parenth, call, loop and builtin rules are expected there.", pair.as_rule());
                 }
             }
         }
-        variable_position
+        Ok(variable_position)
     }
 
     /// Visits a C-factor node in the parse tree (i.e. a factor containing a constant together with a varpow or parenth).
     /// If the constant is not 1, a new variable is created representing this factor and a constraint is added to the R1CS.
-    /// Otherwise, the original variable of the factor is returned.
+    /// Otherwise, the original variable of the factor is returned. A negated factor, as in `2 * -x`, is handled
+    /// by negating the constant.
     /// 
     /// Constraints created by this function are of the form `c * u_j = u_k`, where `c` is the constant,
     /// `u_j` is the variable of the factor and `u_k` is the new variable.
-    fn visit_cfactor(&mut self, cfactor_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_cfactor(&mut self, cfactor_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
             let mut variable_position = 0;
-            let mut variable_constant = 1;
+            let mut variable_constant = F::one();
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
                     Rule::constant => {
-                        variable_constant = pair.as_str().parse().expect("Not a number");
+                        variable_constant = parse_constant(pair.as_str());
+                    }
+                    Rule::negative => {
+                        variable_constant = -variable_constant;
                     }
                     Rule::factor => {
-                        variable_position = s.visit_factor(pair);
+                        variable_position = s.visit_factor(pair)?;
                     }
                     _ => {
-                        panic!("Rule {:?} found in visit_cfactor. Only constant and factor rules are expected there.", pair.as_rule());
+                        panic!("Rule {:?} found in visit_cfactor. Only constant, negative and factor rules are expected there.", pair.as_rule());
                     }
                 }
             }
-            if variable_constant != 1 {
-                let cfactor_variable = s.r1cs.add_variable();
-                s.r1cs.add_constraint(R1CSConstraint::new_constant_multiplication_constraint(variable_constant, variable_position, cfactor_variable));
-                cfactor_variable
+            if variable_constant != F::one() {
+                Ok(lowering::scale(&mut s.r1cs, variable_constant, variable_position))
             } else {
-                variable_position
+                Ok(variable_position)
             }
         }, cfactor_pair)
     }
 
     /// Visits a parenthesis node in the parse tree, and creates the corresponding constraints in the R1CS.
-    fn visit_parenth(&mut self, parenth_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_parenth(&mut self, parenth_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
             let mut exponent = 1;
             let mut variable_position = 0;
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
-                    Rule::expression => {
-                        variable_position = s.visit_expression(pair);
+                    Rule::formula => {
+                        variable_position = s.visit_formula(pair)?;
                     }
                     Rule::exponent => {
-                        exponent = pair.as_str().parse().expect("Not a number");
+                        exponent = s.evaluate_exponent(pair)?;
                     }
                     _ => {
-                        panic!("Rule {:?} found in visit_parenth. Only expression and exponent rules are expected there.", pair.as_rule());
+                        panic!("Rule {:?} found in visit_parenth. Only formula and exponent rules are expected there.", pair.as_rule());
                     }
                 }
             }
-            s.create_exponentiation_constraints(exponent, variable_position)
+            Ok(lowering::power(&mut s.r1cs, variable_position, exponent))
         }, parenth_pair)
     }
 
     /// Visits a product node in the parse tree and creates the corresponding constraints in the R1CS.
-    fn visit_product(&mut self, product_pair: pest::iterators::Pair<Rule>) -> usize {
+    /// 
+    /// Constants and negations appearing after the first factor (as in `(x + 1)6y` or `x * -y`) are
+    /// collected into a single coefficient `c`. If `c` is not 1, the product `p` of the factors is
+    /// multiplied by it using a constraint `c * p = u_k` for a new variable `u_k`.
+    fn visit_product(&mut self, product_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
             let mut variable_positions = Vec::new();
+            let mut coefficient = F::one();
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
                     Rule::cfactor => {
-                        variable_positions.push(s.visit_cfactor(pair));
+                        variable_positions.push(s.visit_cfactor(pair)?);
                     }
                     Rule::factor => {
-                        variable_positions.push(s.visit_factor(pair));
+                        variable_positions.push(s.visit_factor(pair)?);
+                    }
+                    Rule::negative => {
+                        coefficient = -coefficient;
+                    }
+                    Rule::coefficient => {
+                        coefficient = coefficient * parse_constant(pair.as_str());
                     }
                     _ => {
-                        panic!("Rule {:?} found in visit_product. Only cfactor and factor rules are expected there.", pair.as_rule());
+                        panic!("Rule {:?} found in visit_product. Only cfactor, factor, negative and coefficient rules are expected there.", pair.as_rule());
                     }
                 }
             }
-            let mut current_variable = *variable_positions.get(0).expect("Parsed a product without any factors.");
-            for position in variable_positions.iter().skip(1) {
-                let product_variable = s.r1cs.add_variable();
-                s.r1cs.add_constraint(R1CSConstraint::new_multiplication_constraint(current_variable, *position, product_variable));
-                current_variable = product_variable;
+            let mut current_variable = lowering::product(&mut s.r1cs, &variable_positions);
+            if coefficient != F::one() {
+                current_variable = lowering::scale(&mut s.r1cs, coefficient, current_variable);
             }
-            current_variable
+            Ok(current_variable)
         }, product_pair)
     }
 
     /// Visits a term node in the parse tree. If the node is a constant, a new variable is created
-    /// and a constraint is added to the R1CS. Otherwise, the function just returns the variable
-    /// corresponding to the inner node (TODO: It would probably be cleaner to add the new variable
-    /// in a separate "add_constant" function, so that this function does not need to create any
-    /// new constraints).
-    fn visit_term(&mut self, term_pair: pest::iterators::Pair<Rule>) -> usize {
+    /// for it using `add_constant`. Otherwise, the function just returns the variable
+    /// corresponding to the inner node.
+    fn visit_term(&mut self, term_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
             let mut variable_position = 0;
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
                     Rule::cfactor => {
-                        variable_position = s.visit_cfactor(pair);
+                        variable_position = s.visit_cfactor(pair)?;
                     }
                     Rule::product => {
-                        variable_position = s.visit_product(pair);
+                        variable_position = s.visit_product(pair)?;
                     }
                     Rule::constant => {
-                        let constant = pair.as_str().parse().expect("Not a number");
-                        variable_position = s.r1cs.add_variable();
-                        s.r1cs.add_constraint(R1CSConstraint::new_constant_constraint(constant, variable_position));
+                        variable_position = s.add_constant(parse_constant(pair.as_str()));
                     }
                     _ => {
                         panic!("Rule {:?} found in visit_term. Only cfactor, product and constant rules are expected there.", pair.as_rule());
                     }
                 }
             }
-            variable_position
+            Ok(variable_position)
         }, term_pair)
     }
 
@@ -267,39 +1097,39 @@ impl ParseTreeVisitor {
     /// but instead returns the variable corresponding to the node that the term consists of.
     /// 
     /// In particular, this means that the function does not need the wrapper function `cache_wrapper`.
-    fn visit_add_or_sub_term(&mut self, add_or_sub_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_add_or_sub_term(&mut self, add_or_sub_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         let mut variable_position = 0;
         for pair in add_or_sub_pair.into_inner() {
             match pair.as_rule() {
                 Rule::term => {
-                    variable_position = self.visit_term(pair);
+                    variable_position = self.visit_term(pair)?;
                 }
                 _ => {
                     panic!("Rule {:?} found in visit_add_or_sub_term. Only term rules are expected there.", pair.as_rule());
                 }
             }
         }
-        variable_position
+        Ok(variable_position)
     }
 
     /// Visits an expression node in the parse tree and creates the corresponding constraints in the R1CS. 
-    fn visit_expression(&mut self, expression_pair: pest::iterators::Pair<Rule>) -> usize {
+    fn visit_expression(&mut self, expression_pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
         self.cache_wrapper(|s, input_pair| {
-            let mut new_constraint = R1CSSumConstraint::new();
+            let mut sum = LinearCombination::new();
             let mut should_create_new_variable = false;
             let mut fallthrough_variable = 0;
             for pair in input_pair.into_inner() {
                 match pair.as_rule() {
                     Rule::term => {
-                        fallthrough_variable = s.visit_term(pair);
-                        new_constraint.add_to_sum(fallthrough_variable);
+                        fallthrough_variable = s.visit_term(pair)?;
+                        sum.add_term(fallthrough_variable, F::one());
                     }
                     Rule::add_term => {
-                        new_constraint.add_to_sum(s.visit_add_or_sub_term(pair));
+                        sum.add_term(s.visit_add_or_sub_term(pair)?, F::one());
                         should_create_new_variable = true;
                     }
                     Rule::sub_term => {
-                        new_constraint.subtract_from_sum(s.visit_add_or_sub_term(pair));
+                        sum.add_term(s.visit_add_or_sub_term(pair)?, -F::one());
                         should_create_new_variable = true;
                     }
                     _ => {
@@ -308,22 +1138,177 @@ impl ParseTreeVisitor {
                 }
             }
             if should_create_new_variable {
-                let expression_variable = s.r1cs.add_variable();
-                new_constraint.set_right_hand_side(expression_variable);
-                s.r1cs.add_constraint(new_constraint.to_r1cs_constraint());
-                expression_variable
+                Ok(lowering::linear_combination(&mut s.r1cs, sum))
             } else {
-                fallthrough_variable
+                Ok(fallthrough_variable)
             }
         }, expression_pair)
     }  
 
     /// Generates a R1CS system from the visited nodes in the parse tree.
-    fn generate_r1cs(mut self, expression_pair: pest::iterators::Pair<Rule>, expected_result : i64) -> R1CS {
+    fn generate_r1cs(mut self, expression_pair: pest::iterators::Pair<Rule>, expected_result : i64) -> Result<R1CS<F>, String> {
         // Placeholder for generating the R1CS system from the visited nodes
-        let variable_position = self.visit_expression(expression_pair);
-        self.r1cs.add_constraint(R1CSConstraint::new_final_constraint(expected_result, variable_position));
-        self.r1cs
+        let variable_position = self.visit_expression(expression_pair)?;
+        self.r1cs.add_constraint(R1CSConstraint::new_final_constraint(F::from(expected_result), variable_position));
+        Ok(self.r1cs)
+    }
+
+    /// Visits an equation node in the parse tree, i.e. either `p = c` or `p != c` for some
+    /// formula `p` and constant `c`, or a boolean formula (such as a comparison) which should hold,
+    /// and adds the constraint stating that the equation holds.
+    /// 
+    /// The inequality `p != c` is expressed by introducing a new variable `u_k` and the
+    /// constraint `(p - c) * u_k = 1`. This can be satisfied exactly when `p - c` has an
+    /// inverse, i.e. when `p - c != 0`. Witness generation computes `u_k` from a hint.
+    fn visit_equation(&mut self, equation_pair: pest::iterators::Pair<Rule>) -> Result<(), String> {
+        let mut variable_position = 0;
+        let mut formula = String::new();
+        let mut relation = None;
+        let mut constant = F::one();
+        for pair in equation_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::formula => {
+                    formula = pair.as_str().trim().to_string();
+                    variable_position = self.visit_formula(pair)?;
+                }
+                Rule::equals | Rule::not_equals => {
+                    relation = Some(pair.as_rule());
+                }
+                Rule::constant => {
+                    constant = parse_constant(pair.as_str());
+                }
+                _ => {
+                    panic!("Rule {:?} found in visit_equation. Only formula, relation and constant rules are expected there.", pair.as_rule());
+                }
+            }
+        }
+        if relation.is_none() && self.bit_widths.get(&variable_position).is_none_or(|width| *width > 1) {
+            return Err(format!("The equation `{}` is neither a boolean formula nor compares a polynomial to a constant.", formula));
+        }
+        lowering::assert_equation(&mut self.r1cs, variable_position, constant, relation == Some(Rule::not_equals));
+        Ok(())
+    }
+
+    /// Visits a let-binding node `let t = p` in the parse tree, binding the name `t` to the
+    /// variable of the formula `p`. No new constraints are created for the binding itself, but
+    /// the name is recorded in the R1CS, so that the variable can be found by its name. If `p` is
+    /// a vector, the names `t[0]`, `t[1]`, ... are bound to its elements.
+    /// 
+    /// A name can only be bound once, and cannot be bound if it is already used as an input
+    /// variable. In particular, a binding cannot refer to itself, as in `let t = t + 1`.
+    fn visit_let_binding(&mut self, let_binding_pair: pest::iterators::Pair<Rule>) -> Result<(), String> {
+        let mut name = String::new();
+        for pair in let_binding_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::variable => {
+                    name = pair.as_str().to_string();
+                }
+                Rule::formula => {
+                    let variable_position = self.visit_formula(pair)?;
+                    self.bind_name(&name, variable_position)?;
+                }
+                Rule::vector_expression => {
+                    let variable_positions = self.visit_vector_expression(pair)?;
+                    if self.vector_lengths.contains_key(&name) {
+                        return Err(format!("The name `{}` is already bound by a let-binding.", name));
+                    }
+                    for (index, variable_position) in variable_positions.iter().enumerate() {
+                        self.bind_name(&format!("{}[{}]", name, index), *variable_position)?;
+                    }
+                    self.vector_lengths.insert(name.clone(), variable_positions.len());
+                }
+                _ => {
+                    panic!("Rule {:?} found in visit_let_binding. Only variable, formula and vector_expression rules are expected there.", pair.as_rule());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds the name `name` to the variable `u_i`, and records the name in the R1CS.
+    fn bind_name(&mut self, name: &str, i: usize) -> Result<(), String> {
+        if self.bindings.contains_key(name) {
+            return Err(format!("The name `{}` is already bound by a let-binding.", name));
+        }
+        if self.r1cs.get_variable_index(name).is_some() {
+            return Err(format!("The name `{}` cannot be bound, since it is already used as an input variable.", name));
+        }
+        if self.functions.contains_key(name) {
+            return Err(format!("The name `{}` cannot be bound, since it is already used as a function.", name));
+        }
+        self.bindings.insert(name.to_string(), i);
+        self.r1cs.name_variable(name.to_string(), i);
+        Ok(())
+    }
+
+    /// Visits a function definition node `fn f(a_1, ..., a_n) = p` in the parse tree, and records the
+    /// function. No constraints are created until the function is called. Just as for let-bindings,
+    /// the name of the function cannot already be used as an input variable or bound by a let-binding.
+    fn visit_function_definition(&mut self, function_definition_pair: pest::iterators::Pair<'i, Rule>) -> Result<(), String> {
+        let mut name = String::new();
+        let mut parameters: Vec<String> = Vec::new();
+        for pair in function_definition_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::variable => {
+                    name = pair.as_str().to_string();
+                    if self.functions.contains_key(&name) {
+                        return Err(format!("The function `{}` is already defined.", name));
+                    }
+                    if BUILTINS.contains(&name.as_str()) {
+                        return Err(format!("The function `{}` cannot be defined, since it is a builtin.", name));
+                    }
+                    if self.bindings.contains_key(&name) {
+                        return Err(format!("The function `{}` cannot be defined, since its name is already bound by a let-binding.", name));
+                    }
+                    if self.r1cs.get_variable_index(&name).is_some() {
+                        return Err(format!("The function `{}` cannot be defined, since its name is already used as an input variable.", name));
+                    }
+                }
+                Rule::parameters => {
+                    for parameter in pair.into_inner() {
+                        let parameter = parameter.as_str().to_string();
+                        if parameters.contains(&parameter) {
+                            return Err(format!("The parameter `{}` of the function `{}` is given twice.", parameter, name));
+                        }
+                        parameters.push(parameter);
+                    }
+                }
+                Rule::formula => {
+                    self.functions.insert(name.clone(), Function { parameters: parameters.clone(), body: pair });
+                }
+                _ => {
+                    panic!("Rule {:?} found in visit_function_definition. Only variable, parameters and formula rules are expected there.", pair.as_rule());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates a R1CS system from a program node in the parse tree, i.e. a sequence of
+    /// equations, let-bindings and function definitions. The system is satisfied when all the equations hold.
+    /// 
+    /// Errors are prefixed by the line and column of the statement where they occurred,
+    /// and by `path` if the program was read from a file.
+    fn generate_r1cs_from_program(mut self, program_pair: pest::iterators::Pair<'i, Rule>, path: Option<&str>) -> Result<R1CS<F>, String> {
+        for pair in program_pair.into_inner() {
+            let (line, column) = pair.line_col();
+            let result = match pair.as_rule() {
+                Rule::equation => self.visit_equation(pair),
+                Rule::let_binding | Rule::vector_let_binding => self.visit_let_binding(pair),
+                Rule::function_definition => self.visit_function_definition(pair),
+                Rule::EOI => Ok(()),
+                _ => {
+                    panic!("Rule {:?} found in generate_r1cs_from_program. Only equation, let_binding, vector_let_binding and function_definition rules are expected there.", pair.as_rule());
+                }
+            };
+            if let Err(error) = result {
+                return match path {
+                    Some(path) => Err(format!("{}:{}:{}: {}", path, line, column, error)),
+                    None => Err(format!("Line {}, column {}: {}", line, column, error)),
+                };
+            }
+        }
+        Ok(self.r1cs)
     }
 }
 
@@ -332,6 +1317,43 @@ impl ParseTreeVisitor {
 
 
 
+/// Splits a numeric literal following the grammar in *poly.pest* (or *r1cs.pest*) into its sign, its radix
+/// and its digits (without any `_` separators).
+fn split_literal(literal: &str) -> (bool, u32, String) {
+    let (is_negative, literal) = match literal.strip_prefix('-') {
+        Some(literal) => (true, literal),
+        None => (false, literal),
+    };
+    let (radix, digits) = if let Some(digits) = literal.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = literal.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, literal)
+    };
+    (is_negative, radix, digits.replace('_', ""))
+}
+
+/// Parses a numeric literal into a field element. Literals of any size are accepted, and
+/// are reduced modulo the modulus of the field.
+pub(crate) fn parse_constant<F: Field>(literal: &str) -> F {
+    let (is_negative, radix, digits) = split_literal(literal);
+    let value = digits.chars().fold(F::zero(), |value, digit| {
+        value * F::from(radix as u64) + F::from(digit.to_digit(radix).expect("Not a digit") as u64)
+    });
+    if is_negative { -value } else { value }
+}
+
+/// Parses a numeric literal used as a small number, such as an exponent or a number of bits.
+/// An error is returned if the value does not fit in the type `T`. The argument `usage` is
+/// used in the error message, describing what the number is used as.
+fn parse_small_number<T: TryFrom<u64>>(literal: &str, usage: &str) -> Result<T, String> {
+    let (_, radix, digits) = split_literal(literal);
+    u64::from_str_radix(&digits, radix).ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or(format!("The number `{}` is too large to be used as {}.", literal, usage))
+}
+
 /// Parses a polynomial and generates a R1CS system from it.
 /// 
 /// This is the main function of the r1thm library. It takes a polynomial, given as a 
@@ -347,15 +1369,76 @@ pub fn poly2r1cs(polynomial: String, expected_result : i64) -> Result<R1CS, Stri
         Ok(mut pairs) => {
             let expression = pairs.next().unwrap();
 
-            Ok(visitor.generate_r1cs(expression, expected_result))
+            visitor.generate_r1cs(expression, expected_result)
         }
         Err(e) => Err(format!("Error parsing polynomial: {}", e)),
     }
 }
 
+/// Parses an equation and generates a R1CS system from it.
+/// 
+/// The equation is either of the form `p = c` or `p != c`, where `p` is a polynomial
+/// following the grammar in *poly.pest*, and `c` is a constant. Hence, `equation2r1cs("x^2 = 4")`
+/// gives the same system as `poly2r1cs("x^2", 4)`, while `equation2r1cs("x^2 != 4")` gives a
+/// system which is satisfied exactly when `x^2` differs from `4`. The equation may also be a
+/// comparison such as `bits(x, 8) >= 18`, which gives a system satisfied when the comparison holds.
+/// 
+/// More generally, the input may be a program of several equations and let-bindings separated
+/// by semicolons, such as `let t = x^2 + y; t^3 + 2t = 17`. The system is then satisfied when all
+/// the equations hold, and the variables of the let-bindings can be found by their names. Programs
+/// may also define functions, such as `fn round(x, k) = (x + k)^7`, whose calls are inlined.
+pub fn equation2r1cs(equation: String) -> Result<R1CS, String> {
+    program2r1cs(&equation, None)
+}
+
+/// Reads a program from the file at `path` and generates a R1CS system from it, just as `equation2r1cs`.
+/// 
+/// Programs in files typically span several lines, and may contain comments (`// ...` and `/* ... */`).
+/// Errors include the path of the file, together with the line and column where they occurred.
+pub fn poly2r1cs_file<P: AsRef<Path>>(path: P) -> Result<R1CS, String> {
+    let path = path.as_ref().display().to_string();
+    let program = std::fs::read_to_string(&path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    program2r1cs(&program, Some(&path))
+}
+
+/// Parses a single term of a polynomial (without a leading sign), and adds the constraints for it
+/// to an existing R1CS system. The variable of the term is returned.
+/// 
+/// This is used by the streaming front-end (see the module `streaming`), which compiles a polynomial
+/// one term at a time. Since a new visitor is used for each term, no nodes are shared between terms.
+pub(crate) fn term2r1cs<F: Field>(r1cs: &mut R1CS<F>, term: &str) -> Result<usize, String> {
+    let mut visitor = ParseTreeVisitor::with_r1cs(std::mem::take(r1cs));
+    let result = match PolyParser::parse(Rule::single_term, term) {
+        Ok(mut pairs) => {
+            let term = pairs.next().unwrap().into_inner().next().unwrap();
+            visitor.visit_term(term)
+        }
+        Err(e) => Err(format!("Error parsing term: {}", e)),
+    };
+    *r1cs = visitor.r1cs;
+    result
+}
+
+/// Parses a program and generates a R1CS system from it. If the program was read from a file,
+/// `path` is the path of the file, which is then included in the error messages.
+fn program2r1cs(program: &str, path: Option<&str>) -> Result<R1CS, String> {
+    let visitor = ParseTreeVisitor::new();
+    match PolyParser::parse(Rule::program, program) {
+        Ok(mut pairs) => {
+            let program = pairs.next().unwrap();
+            visitor.generate_r1cs_from_program(program, path)
+        }
+        Err(e) => match path {
+            Some(path) => Err(format!("Error parsing {}: {}", path, e.with_path(path))),
+            None => Err(format!("Error parsing equation: {}", e)),
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::field::Bn254;
     
     /// Helper function to test that the system has the correct variables, as well as returning
     /// the indices of their variables.
@@ -384,8 +1467,8 @@ mod tests {
         let matching_constraint = matching_constraint.unwrap();
         let c_pairs = matching_constraint.get_c_pairs();
         assert_eq!(c_pairs.len(), 1, "Expected exactly one entry in c");
-        let (c_key, c_value) = c_pairs.iter().next().unwrap();
-        assert_eq!(*c_value, 1, "Expected c value to be 1");
+        let (c_key, c_value) = c_pairs.first().unwrap();
+        assert_eq!(*c_value, Bn254::one(), "Expected c value to be 1");
         *c_key
     }
 
@@ -467,7 +1550,7 @@ mod tests {
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
         pos.insert("67x".to_string(), handle_const_mult_constraint(&parsed_poly, 67, pos["x"]));
         pos.insert("7*y".to_string(), handle_const_mult_constraint(&parsed_poly, 7, pos["y"]));
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["67x"],1), (pos["7*y"],1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
     }
@@ -477,12 +1560,12 @@ mod tests {
         let polynomial = "7 + 2x + 3y".to_string();
         let expected_result = 70;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
         pos.insert("7".to_string(), handle_const_constraint(&parsed_poly, 7));
         pos.insert("2x".to_string(), handle_const_mult_constraint(&parsed_poly, 2, pos["x"]));
         pos.insert("3y".to_string(), handle_const_mult_constraint(&parsed_poly, 3, pos["y"]));
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["7"],1), (pos["2x"],1), (pos["3y"],1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
     }
@@ -492,7 +1575,7 @@ mod tests {
         let polynomial = "x*y".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
         let final_variable = handle_mult_constraint(&parsed_poly, pos["x"], pos["y"]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
@@ -503,7 +1586,7 @@ mod tests {
         let polynomial = "x*y*z*a*b*c".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string(), "z".to_string(), "a".to_string(), "b".to_string(), "c".to_string()]);
         pos.insert("x*y".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["y"]));
         pos.insert("x*y*z".to_string(), handle_mult_constraint(&parsed_poly, pos["x*y"], pos["z"]));
@@ -518,7 +1601,7 @@ mod tests {
         let polynomial = "x^2".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         let final_variable = handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
@@ -529,7 +1612,7 @@ mod tests {
         let polynomial = "x^8".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         let mut final_variable = pos["x"];
         for _ in 0..3 {
@@ -543,7 +1626,7 @@ mod tests {
         let polynomial = "x^3".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         pos.insert("x^2".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]));
         let final_variable = handle_mult_constraint(&parsed_poly, pos["x"], pos["x^2"]);
@@ -555,7 +1638,7 @@ mod tests {
         let polynomial = "x^13".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         pos.insert("x^2".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]));
         pos.insert("x^3".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x^2"]));
@@ -570,7 +1653,7 @@ mod tests {
         let polynomial = "(x + y) + z".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string(), "z".to_string()]);
         pos.insert("(x + y)".to_string(), handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"], 1)]));
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["(x + y)"],1), (pos["z"],1)]);
@@ -582,7 +1665,7 @@ mod tests {
         let polynomial = "(x + y)^7".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
         pos.insert("(x + y)".to_string(), handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],1)]));
         pos.insert("(x + y)^2".to_string(), handle_mult_constraint(&parsed_poly, pos["(x + y)"], pos["(x + y)"]));  
@@ -597,7 +1680,7 @@ mod tests {
         let polynomial = "x + x*x".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         pos.insert("x*x".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]));
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["x*x"],1)]);
@@ -609,7 +1692,7 @@ mod tests {
         let polynomial = "(89(6x^3)^4)^2".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         pos.insert("x^2".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]));
         pos.insert("x^3".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x^2"]));
@@ -626,7 +1709,7 @@ mod tests {
         let polynomial = "(7x^5)^4(5x+t)^8*4x".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let mut pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "t".to_string()]);
         pos.insert("x^2".to_string(), handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]));
         pos.insert("x^4".to_string(), handle_mult_constraint(&parsed_poly, pos["x^2"], pos["x^2"]));
@@ -641,7 +1724,7 @@ mod tests {
         let polynomial = "x - y".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],-1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
@@ -652,7 +1735,7 @@ mod tests {
         let polynomial = "x - y - z + a - b + t + s - p".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string(), "z".to_string(), "a".to_string(), "b".to_string(), "t".to_string(), "s".to_string(), "p".to_string()]);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],-1), (pos["z"],-1), (pos["a"],1), (pos["b"],-1), (pos["t"],1), (pos["s"],1), (pos["p"],-1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
@@ -663,7 +1746,7 @@ mod tests {
         let polynomial = "-x - y + z".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string(), "z".to_string()]);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],-1), (pos["y"],-1), (pos["z"],1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
@@ -674,10 +1757,514 @@ mod tests {
         let polynomial = "-x".to_string();
         let expected_result = 10;
         let parsed_poly = parse(polynomial, expected_result);
-        parsed_poly.print();
+        println!("{}", parsed_poly);
         let pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
         let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],-1)]);
         check_final_constraint(&parsed_poly, final_variable, expected_result);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_equation () {
+        let parsed_poly = equation2r1cs("x*y = 12".to_string()).unwrap();
+        println!("{}", parsed_poly);
+        let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
+        let final_variable = handle_mult_constraint(&parsed_poly, pos["x"], pos["y"]);
+        check_final_constraint(&parsed_poly, final_variable, 12);
+    }
+
+    #[test]
+    fn test_inequality () {
+        let parsed_poly = equation2r1cs("x + y != 5".to_string()).unwrap();
+        println!("{}", parsed_poly);
+        let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
+        let sum_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],1)]);
+        let expected_a = HashMap::from([(sum_variable, 1), (0, -5)]);
+        let inverse_constraint = parsed_poly.constraints().find(|constraint| constraint.rhs_matches(&HashMap::from([(0, 1)])) && constraint.lhs_matches(&expected_a, &HashMap::from([(sum_variable + 1, 1)])));
+        assert!(inverse_constraint.is_some(), "No inverse constraint found");
+    }
+
+    #[test]
+    fn test_invalid_equation () {
+        assert!(equation2r1cs("x + y".to_string()).is_err());
+        assert!(equation2r1cs("x + y != z".to_string()).is_err());
+        assert!(equation2r1cs("x + y == 5".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_bool_builtin () {
+        let parsed_poly = parse("bool(x) + y".to_string(), 3);
+        println!("{}", parsed_poly);
+        let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
+        let expected_b = HashMap::from([(0, 1), (pos["x"], -1)]);
+        assert!(parsed_poly.find_matching_constraint(&HashMap::from([(pos["x"], 1)]), &expected_b, Some(&HashMap::new())).is_some());
+        let final_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],1)]);
+        check_final_constraint(&parsed_poly, final_variable, 3);
+    }
+
+    #[test]
+    fn test_bits_builtin () {
+        let parsed_poly = parse("(bits(x + y, 3))^2".to_string(), 9);
+        println!("{}", parsed_poly);
+        let pos = get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
+        let sum_variable = handle_add_constraint(&parsed_poly, vec![(pos["x"],1), (pos["y"],1)]);
+        let bit_decomposition = parsed_poly.constraints().find(|constraint| constraint.rhs_matches(&HashMap::from([(sum_variable, 1)])) && constraint.get_b_values().len() == 3);
+        assert!(bit_decomposition.is_some(), "No bit decomposition constraint found");
+        let mut powers = bit_decomposition.unwrap().get_b_values();
+        powers.sort_by_key(|value| value.bit_length());
+        assert_eq!(powers, vec![Bn254::from(1u64), Bn254::from(2u64), Bn254::from(4u64)]);
+        let final_variable = handle_mult_constraint(&parsed_poly, sum_variable, sum_variable);
+        check_final_constraint(&parsed_poly, final_variable, 9);
+    }
+
+    #[test]
+    fn test_builtin_witness () {
+        let parsed_poly = parse("bits(x, 4) * bool(y)".to_string(), 7);
+        let inputs = |x: i64, y: i64| HashMap::from([("x".to_string(), Bn254::from(x)), ("y".to_string(), Bn254::from(y))]);
+        assert!(parsed_poly.generate_witness(&inputs(7, 1)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(7, 2)).is_err());
+        let parsed_poly = parse("bits(x, 4) + y".to_string(), 17);
+        assert!(parsed_poly.generate_witness(&inputs(15, 2)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(16, 1)).is_err());
+    }
+
+    #[test]
+    fn test_comparison_statement () {
+        let parsed_poly = equation2r1cs("bits(a, 7) >= 18".to_string()).unwrap();
+        println!("{}", parsed_poly);
+        let inputs = |a: i64| HashMap::from([("a".to_string(), Bn254::from(a))]);
+        assert!(parsed_poly.generate_witness(&inputs(18)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(100)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(17)).is_err());
+        assert!(parsed_poly.generate_witness(&inputs(128)).is_err());
+    }
+
+    #[test]
+    fn test_comparison_in_polynomial () {
+        let parsed_poly = parse("(bits(x, 8) < bits(y, 4)) * z + (x <= y) + (x > 3)".to_string(), 7);
+        let inputs = |x: i64, y: i64, z: i64| HashMap::from([("x".to_string(), Bn254::from(x)), ("y".to_string(), Bn254::from(y)), ("z".to_string(), Bn254::from(z))]);
+        assert!(parsed_poly.generate_witness(&inputs(2, 3, 6)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(0, 1, 6)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(5, 6, 5)).is_ok());
+        assert!(parsed_poly.generate_witness(&inputs(5, 6, 6)).is_err());
+    }
+
+    #[test]
+    fn test_comparison_without_bit_width () {
+        let error = equation2r1cs("x < 5".to_string()).err().unwrap();
+        assert!(error.contains("bits"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("bits(x, 3) < 5".to_string()).is_ok());
+        assert!(poly2r1cs("(x < bits(y, 3))".to_string(), 1).is_err());
+        assert!(equation2r1cs("bits(x, 253) < 5".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_too_many_bits() {
+        assert!(equation2r1cs("bits(x, 253) = 5".to_string()).is_ok());
+        for size in [254, 300] {
+            let error = equation2r1cs(format!("bits(x, {}) = 5", size)).err().unwrap();
+            assert_eq!(error, format!("Line 1, column 1: Cannot compile `bits(x, {})`, since {} bits do not restrict the values of a field with a 254 bit modulus.", size, size));
+        }
+    }
+
+    /// Helper function to create the inputs of a witness from pairs of variable names and values.
+    fn witness_inputs(values: &[(&str, i64)]) -> HashMap<String, Bn254> {
+        values.iter().map(|(name, value)| (name.to_string(), Bn254::from(*value))).collect()
+    }
+
+    #[test]
+    fn test_conditional () {
+        let parsed_poly = equation2r1cs("(if b then x^2 else x + 1) * y = 18".to_string()).unwrap();
+        println!("{}", parsed_poly);
+        let pos = get_variable_positions(&parsed_poly, vec!["b".to_string(), "x".to_string(), "y".to_string()]);
+        let expected_b = HashMap::from([(0, 1), (pos["b"], -1)]);
+        assert!(parsed_poly.find_matching_constraint(&HashMap::from([(pos["b"], 1)]), &expected_b, Some(&HashMap::new())).is_some(), "The condition is not constrained to be boolean");
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("b", 1), ("x", 3), ("y", 2)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("b", 0), ("x", 2), ("y", 6)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("b", 0), ("x", 3), ("y", 2)])).is_err());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("b", 2), ("x", 3), ("y", 2)])).is_err());
+    }
+
+    #[test]
+    fn test_boolean_operators () {
+        let parsed_poly = equation2r1cs("a and not b or c xor d".to_string()).unwrap();
+        for a in 0..2 {
+            for b in 0..2 {
+                for c in 0..2 {
+                    for d in 0..2 {
+                        let expected = (a == 1 && b == 0) || (c != d);
+                        let result = parsed_poly.generate_witness(&witness_inputs(&[("a", a), ("b", b), ("c", c), ("d", d)]));
+                        assert_eq!(result.is_ok(), expected, "a = {}, b = {}, c = {}, d = {}", a, b, c, d);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_boolean_formula_with_comparisons () {
+        let parsed_poly = equation2r1cs("(bits(x, 4) < 10 and (x > 2 or y)) + (if x > 8 then 2 else 0) = 1".to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 7), ("y", 0)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 1)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 0)])).is_err());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 9), ("y", 0)])).is_err());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 12), ("y", 1)])).is_err());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 7), ("y", 2)])).is_err());
+    }
+
+    #[test]
+    fn test_non_boolean_operands () {
+        let error = equation2r1cs("x and 2".to_string()).err().unwrap();
+        assert!(error.contains("`2`"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("not 3".to_string()).is_err());
+        assert!(equation2r1cs("if 2 then x else y = 1".to_string()).is_err());
+        assert!(equation2r1cs("x and 1".to_string()).is_ok());
+        assert!(equation2r1cs("x + y".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_keywords_are_not_variables () {
+        assert!(equation2r1cs("x and y".to_string()).unwrap().get_variable_index("a").is_none());
+        let parsed_poly = parse("a*n*d + o r".to_string(), 1);
+        get_variable_positions(&parsed_poly, vec!["a".to_string(), "n".to_string(), "d".to_string(), "o".to_string(), "r".to_string()]);
+    }
+
+    #[test]
+    fn test_let_binding () {
+        let parsed_poly = equation2r1cs("let t = x^2 + y; t^3 + 2t = 12".to_string()).unwrap();
+        println!("{}", parsed_poly);
+        get_variable_positions(&parsed_poly, vec!["x".to_string(), "y".to_string()]);
+        let t = parsed_poly.get_variable_index("t").expect("The bound name t should be recorded");
+        // t = x^2 + y = 2 satisfies t^3 + 2t = 12, while t = 1 does not.
+        let witness = parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 1)])).unwrap();
+        assert_eq!(witness[t], Bn254::from(2u64));
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 0)])).is_err());
+    }
+
+    #[test]
+    fn test_program_with_several_statements () {
+        let parsed_poly = equation2r1cs("let s = bits(x, 4) + bits(y, 4); let p = x*y; bits(s, 5) < 10; p != 0; p = 12;".to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 3), ("y", 4)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 2), ("y", 6)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 12), ("y", 1)])).is_err());
+        // Bound names are not inputs.
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 3), ("y", 4), ("p", 12)])).is_err());
+    }
+
+    #[test]
+    fn test_invalid_let_bindings () {
+        assert!(equation2r1cs("let t = t + 1; t = 2".to_string()).is_err());
+        assert!(equation2r1cs("let t = x; let t = y; t = 2".to_string()).is_err());
+        assert!(equation2r1cs("x = 2; let x = y; x = 2".to_string()).is_err());
+        assert!(equation2r1cs("let l = x; l = 2".to_string()).is_ok());
+        assert!(equation2r1cs("let = x; x = 2".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_identifiers () {
+        let parsed_poly = parse("alpha*balance_in + x1 y - utxo^2 + order".to_string(), 1);
+        let pos = get_variable_positions(&parsed_poly, vec!["alpha".to_string(), "balance_in".to_string(), "x1".to_string(), "y".to_string(), "utxo".to_string(), "order".to_string()]);
+        handle_mult_constraint(&parsed_poly, pos["alpha"], pos["balance_in"]);
+        handle_mult_constraint(&parsed_poly, pos["x1"], pos["y"]);
+        handle_mult_constraint(&parsed_poly, pos["utxo"], pos["utxo"]);
+        let parsed_poly = parse("xy".to_string(), 1);
+        get_variable_positions(&parsed_poly, vec!["xy".to_string()]);
+        assert!(equation2r1cs("android and x_or".to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_printed_names_do_not_collide () {
+        let parsed_poly = equation2r1cs("let t = u_1 * u_2; t + x = 5".to_string()).unwrap();
+        let names = parsed_poly.variable_names();
+        assert_eq!(names[parsed_poly.get_variable_index("u_1").unwrap()], "u_1");
+        assert_eq!(names[parsed_poly.get_variable_index("t").unwrap()], "t");
+        let unnamed: Vec<&String> = names.iter().filter(|name| !["u_1", "u_2", "t", "x"].contains(&name.as_str())).collect();
+        assert!(!unnamed.is_empty());
+        assert!(unnamed.iter().all(|name| name.starts_with("u__")), "Unexpected names {:?}", unnamed);
+        let parsed_poly = parse("x*y".to_string(), 1);
+        assert_eq!(parsed_poly.variable_names()[0], "u_0");
+    }
+
+    #[test]
+    fn test_numeric_literals () {
+        for equation in ["x = 0x1f", "x = 0b1_1111", "x = 31", "x = 0x_1f", "0x2x = 62", "1_000x = 31_000"] {
+            let parsed_poly = equation2r1cs(equation.to_string());
+            assert!(parsed_poly.is_ok() == (equation != "x = 0x_1f"), "Unexpected result for {}", equation);
+            if let Ok(parsed_poly) = parsed_poly {
+                assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 31)])).is_ok(), "{} should hold for x = 31", equation);
+                assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 30)])).is_err(), "{} should not hold for x = 30", equation);
+            }
+        }
+        let parsed_poly = equation2r1cs("x^0b11 + x^0x2 = 12".to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 2)])).is_ok());
+    }
+
+    #[test]
+    fn test_large_constants_are_reduced () {
+        // The modulus of the BN254 scalar field, plus one.
+        let modulus_plus_one = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000002";
+        let parsed_poly = equation2r1cs(format!("{}x = 5", modulus_plus_one)).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 5)])).is_ok());
+        let parsed_poly = equation2r1cs(format!("x + 1 = {}", modulus_plus_one)).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 0)])).is_ok());
+        let parsed_poly = equation2r1cs("x = -0x10".to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", -16)])).is_ok());
+    }
+
+    #[test]
+    fn test_large_exponents_are_rejected () {
+        let error = equation2r1cs("x^100000000000 = 1".to_string()).err().unwrap();
+        assert!(error.contains("too large to be used as an exponent"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("(x + 1)^0xffff_ffff_ffff = 1".to_string()).is_err());
+        let error = equation2r1cs("bits(x, 0x1_0000_0000_0000_0000) < 3".to_string()).err().unwrap();
+        assert!(error.contains("too large to be used as a number of bits"), "Unexpected error: {}", error);
+    }
+
+    /// Checks that the equation holds for the given inputs, and does not hold when `x` is increased by one.
+    fn check_equation(equation: &str, values: &[(&str, i64)]) {
+        let parsed_poly = equation2r1cs(equation.to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(values)).is_ok(), "{} should hold for {:?}", equation, values);
+        let modified: Vec<(&str, i64)> = values.iter().map(|(name, value)| (*name, if *name == "x" { value + 1 } else { *value })).collect();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&modified)).is_err(), "{} should not hold for {:?}", equation, modified);
+    }
+
+    #[test]
+    fn test_implicit_multiplication () {
+        check_equation("(x + 1)6y = 36", &[("x", 2), ("y", 2)]);
+        check_equation("(x + 1)6 = 18", &[("x", 2)]);
+        check_equation("x * 6 - 1 = 11", &[("x", 2)]);
+        check_equation("(x + 1)(y - 1)2 = 12", &[("x", 2), ("y", 3)]);
+        // A juxtaposed constant is not negative, so this is the difference of x and 2.
+        check_equation("x -2 = 1", &[("x", 3)]);
+    }
+
+    #[test]
+    fn test_negated_factors () {
+        check_equation("z * -(x + y) = -15", &[("x", 2), ("y", 3), ("z", 3)]);
+        check_equation("3 * -x^2 * y = -24", &[("x", 2), ("y", 2)]);
+        check_equation("2 * -x = -4", &[("x", 2)]);
+        check_equation("y * -x * -2 = 12", &[("x", 2), ("y", 3)]);
+        check_equation("-x^2 * y = -12", &[("x", 2), ("y", 3)]);
+        check_equation("2 - x = -1", &[("x", 3)]);
+    }
+
+    #[test]
+    fn test_double_star_exponent () {
+        check_equation("x**3 + (x + 1) ** 2 = 17", &[("x", 2)]);
+        let parsed_poly = parse("x**3".to_string(), 8);
+        let pos = get_variable_positions(&parsed_poly, vec!["x".to_string()]);
+        let square = handle_mult_constraint(&parsed_poly, pos["x"], pos["x"]);
+        handle_mult_constraint(&parsed_poly, pos["x"], square);
+    }
+
+    #[test]
+    fn test_comments_and_newlines () {
+        let program = "// The sum of two bits.\nlet s = bool(x) /* first */ + bool(y);\n\ns = 1 // exactly one\n";
+        let parsed_poly = equation2r1cs(program.to_string()).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 0)])).is_ok());
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 1)])).is_err());
+        let error = equation2r1cs("x = 1;\n  x < 3".to_string()).err().unwrap();
+        assert!(error.starts_with("Line 2, column 3: "), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_poly2r1cs_file () {
+        let path = std::env::temp_dir().join(format!("r1thm_test_{}.poly", std::process::id()));
+        std::fs::write(&path, "/* A small program. */\nlet t = x^2 + y;\nt^3 + 2t = 12;\n").unwrap();
+        let parsed_poly = poly2r1cs_file(&path).unwrap();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x", 1), ("y", 1)])).is_ok());
+
+        std::fs::write(&path, "let t = x;\nbits(t, 3) < y;\n").unwrap();
+        let error = poly2r1cs_file(&path).err().unwrap();
+        assert!(error.starts_with(&format!("{}:2:1: ", path.display())), "Unexpected error: {}", error);
+
+        std::fs::write(&path, "let t = x;\nt = = 2;\n").unwrap();
+        let error = poly2r1cs_file(&path).err().unwrap();
+        assert!(error.contains(&format!("{}:2:", path.display())), "Unexpected error: {}", error);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(poly2r1cs_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_functions () {
+        check_equation("fn mimc_round(x, k) = (x + k)^2; mimc_round(mimc_round(x, 1), 2) = 324", &[("x", 3)]);
+        check_equation("fn f(a) = a^2; fn g(a, b) = f(a) - f(b); g(x, y) + f(y) = 9", &[("x", 3), ("y", 5)]);
+        check_equation("fn f(a) = a^2; x * f(y)^2 = 32", &[("x", 2), ("y", 2)]);
+        // Without a function named x, a call is a product.
+        check_equation("x(y + 1)^2 = 48", &[("x", 3), ("y", 3)]);
+    }
+
+    #[test]
+    fn test_function_instantiations () {
+        // Each call with new arguments creates new variables.
+        let single = equation2r1cs("fn f(a) = a^2; f(x) = 4".to_string()).unwrap();
+        let different = equation2r1cs("fn f(a) = a^2; f(x) + f(y) = 4".to_string()).unwrap();
+        let identical = equation2r1cs("fn f(a) = a^2; f(x) + f( x ) = 8".to_string()).unwrap();
+        // The systems consist of the squares, the sum (if any) and the final constraint.
+        assert_eq!(single.size(), 2);
+        assert_eq!(different.size(), 4);
+        assert_eq!(identical.size(), 3);
+        check_equation("fn f(a) = a^2; f(x) + f( x ) = 8", &[("x", 2)]);
+        check_equation("x + x - y + 2x = 10", &[("x", 3), ("y", 2)]);
+    }
+
+    #[test]
+    fn test_invalid_functions () {
+        let error = equation2r1cs("fn f(a) = f(a) + 1; f(x) = 2".to_string()).err().unwrap();
+        assert!(error.contains("called recursively"), "Unexpected error: {}", error);
+        let error = equation2r1cs("fn f(a) = g(a); fn g(a) = 2f(a); f(x) = 2".to_string()).err().unwrap();
+        assert!(error.contains("called recursively"), "Unexpected error: {}", error);
+        let error = equation2r1cs("fn f(a) = a + z; f(x) = 2".to_string()).err().unwrap();
+        assert!(error.contains("not a parameter"), "Unexpected error: {}", error);
+        let error = equation2r1cs("fn f(a, b) = a*b; f(x) = 2".to_string()).err().unwrap();
+        assert!(error.contains("takes 2 arguments"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("f(x, y) = 2".to_string()).is_err());
+        assert!(equation2r1cs("fn f(a) = a; fn f(a) = 2a; f(x) = 2".to_string()).is_err());
+        assert!(equation2r1cs("fn f(a, a) = a; f(x, x) = 2".to_string()).is_err());
+        for builtin in BUILTINS {
+            let error = equation2r1cs(format!("fn {}(a, b) = a*b; {}(x, y) = 1", builtin, builtin)).err().unwrap();
+            assert!(error.ends_with(&format!("The function `{}` cannot be defined, since it is a builtin.", builtin)), "Unexpected error: {}", error);
+        }
+    }
+
+    #[test]
+    fn test_function_names_of_variables () {
+        let error = equation2r1cs("let t = f(x); fn f(a) = a + 1; f(x) = 5".to_string()).err().unwrap();
+        assert!(error.ends_with("The function `f` cannot be defined, since its name is already used as an input variable."), "Unexpected error: {}", error);
+        let error = equation2r1cs("let f = x; fn f(a) = a + 1; f(x) = 5".to_string()).err().unwrap();
+        assert!(error.contains("already bound by a let-binding"), "Unexpected error: {}", error);
+        let error = equation2r1cs("fn f(a) = a + 1; f(x) + f = 5".to_string()).err().unwrap();
+        assert!(error.ends_with("The function `f` cannot be used as an input variable."), "Unexpected error: {}", error);
+        let error = equation2r1cs("fn f(a) = a + 1; let f = x; f(x) = 5".to_string()).err().unwrap();
+        assert!(error.contains("already used as a function"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_indexed_variables () {
+        let parsed_poly = equation2r1cs("x[0] * x[1] + x[2 * 3 - 1] = 7".to_string()).unwrap();
+        get_variable_positions(&parsed_poly, vec!["x[0]".to_string(), "x[1]".to_string(), "x[5]".to_string()]);
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("x[0]", 2), ("x[1]", 3), ("x[5]", 1)])).is_ok());
+        let error = equation2r1cs("x[i] = 1".to_string()).err().unwrap();
+        assert!(error.contains("not a loop index"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("x[0 - 1] = 1".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_sum_and_product () {
+        let parsed_poly = equation2r1cs("sum(i in 0..4, c[i] * x[i]^2) = 14".to_string()).unwrap();
+        get_variable_positions(&parsed_poly, (0..4).flat_map(|i| [format!("c[{}]", i), format!("x[{}]", i)]).collect());
+        let inputs: Vec<(String, i64)> = (0..4).flat_map(|i| [(format!("c[{}]", i), 1), (format!("x[{}]", i), i)]).collect();
+        let inputs: Vec<(&str, i64)> = inputs.iter().map(|(name, value)| (name.as_str(), *value)).collect();
+        assert!(parsed_poly.generate_witness(&witness_inputs(&inputs)).is_ok());
+
+        // Polynomial evaluation, using the loop index as a constant and in exponents.
+        check_equation("sum(i in 0..4, (i + 1) * x^i) = 49", &[("x", 2)]);
+        check_equation("product(i in 1..4, x + i) = 60", &[("x", 2)]);
+        check_equation("sum(i in 0..3, sum(j in i..3, x[j])) + x = 14", &[("x[0]", 1), ("x[1]", 2), ("x[2]", 3), ("x", 0)]);
+        check_equation("sum(i in 0..0, x) + product(i in 0..0, x) + x = 3", &[("x", 2)]);
+    }
+
+    #[test]
+    fn test_loop_iterations_are_distinct () {
+        // The body has the same text in each iteration, but must create new variables.
+        let parsed_poly = equation2r1cs("sum(i in 0..3, y^2 * x[i]) = 6".to_string()).unwrap();
+        // y^2 is computed once per iteration, but the products, sum and final constraint are distinct.
+        assert_eq!(parsed_poly.size(), 3 + 3 + 1 + 1);
+        assert!(parsed_poly.generate_witness(&witness_inputs(&[("y", 1), ("x[0]", 1), ("x[1]", 2), ("x[2]", 3)])).is_ok());
+        let error = equation2r1cs("x^(y + 1) = 1".to_string()).err().unwrap();
+        assert!(error.contains("not a loop index"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_dot_product () {
+        let parsed_poly = equation2r1cs("dot(a[0..3], b[0..3]) = 20".to_string()).unwrap();
+        // One multiplication per element, one sum and the final constraint.
+        assert_eq!(parsed_poly.size(), 3 + 1 + 1);
+        let witness = parsed_poly.generate_witness(&witness_inputs(&[("a[0]", 1), ("a[1]", 2), ("a[2]", 3), ("b[0]", 4), ("b[1]", 2), ("b[2]", 4)]));
+        assert!(witness.is_ok());
+        check_equation("dot([x, 2, x + 1], [3, x, 1]) = 13", &[("x", 2)]);
+    }
+
+    #[test]
+    fn test_vector_operators () {
+        check_equation("let v = [x, y] .* [y, 2] + [1, x]; dot(v[..], [1, 1]) = 15", &[("x", 2), ("y", 3)]);
+        check_equation("let v = ([x, y] - [1, 1]) .* [2, 3]; v[0] + v[1] = 8", &[("x", 2), ("y", 3)]);
+        // A matrix-vector product, where each row of m is a slice.
+        let program = "let r = [dot(m[0..2], x[0..2]), dot(m[2..4], x[0..2])]; r[0] = 5; r[1] = 11";
+        let parsed_poly = equation2r1cs(program.to_string()).unwrap();
+        let witness = parsed_poly.generate_witness(&witness_inputs(&[("m[0]", 1), ("m[1]", 2), ("m[2]", 3), ("m[3]", 4), ("x[0]", 1), ("x[1]", 2)]));
+        assert!(witness.is_ok());
+    }
+
+    #[test]
+    fn test_invalid_vectors () {
+        let error = equation2r1cs("dot([x, y], [x]) = 1".to_string()).err().unwrap();
+        assert!(error.contains("lengths 2 and 1"), "Unexpected error: {}", error);
+        let error = equation2r1cs("let v = [x] .* y[0..2]; v[0] = 1".to_string()).err().unwrap();
+        assert!(error.contains("lengths 1 and 2"), "Unexpected error: {}", error);
+        let error = equation2r1cs("dot(x[..], x[..]) = 1".to_string()).err().unwrap();
+        assert!(error.contains("length of `x` is unknown"), "Unexpected error: {}", error);
+        assert!(equation2r1cs("let v = [x, y]; let v = [y]; v[0] = 1".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_misused_vectors () {
+        let error = equation2r1cs("let v = [x, y]; v[2] = 1".to_string()).err().unwrap();
+        assert!(error.ends_with("The index 2 is out of bounds for the vector `v` of length 2."), "Unexpected error: {}", error);
+        let error = equation2r1cs("let v = [x, y]; dot(v[1..3], [1, 2]) = 1".to_string()).err().unwrap();
+        assert!(error.contains("out of bounds"), "Unexpected error: {}", error);
+        let error = equation2r1cs("let v = [x, y]; v = 1".to_string()).err().unwrap();
+        assert!(error.contains("The name `v` is bound to a vector of length 2, so it cannot be used as a scalar."), "Unexpected error: {}", error);
+        let error = equation2r1cs("let v = [x, y]; sum(i in 0..3, v[i]) = 1".to_string()).err().unwrap();
+        assert!(error.contains("out of bounds"), "Unexpected error: {}", error);
+        check_equation("let v = [x, y]; sum(i in 0..2, v[i]) + v[1] = 7", &[("x", 1), ("y", 3)]);
+    }
+
+    #[test]
+    fn test_deterministic_systems () {
+        let program = "let t = z*y + x; fn f(a, b) = a*b - 3; sum(i in 0..4, w[i] * f(t, i)) != 7";
+        let first = equation2r1cs(program.to_string()).unwrap();
+        let inputs: Vec<(&str, usize)> = first.inputs().collect();
+        let names: Vec<&str> = inputs.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["z", "y", "x", "w[0]", "w[1]", "w[2]", "w[3]"]);
+        for _ in 0..10 {
+            let other = equation2r1cs(program.to_string()).unwrap();
+            assert_eq!(other.inputs().collect::<Vec<_>>(), inputs);
+            assert_eq!(other.variable_names(), first.variable_names());
+            assert!(other.constraints().eq(first.constraints()));
+        }
+    }
+
+    #[test]
+    fn test_node_ids () {
+        // The parentheses are identified by their text, and by their parts once they are longer.
+        let long_sum = (0..20).map(|i| format!("x_{}", i)).collect::<Vec<_>>().join(" + ");
+        for sum in ["x + 1".to_string(), long_sum] {
+            let mut nodes = NodeInterner::default();
+            let program = format!("({})^2 + 3({})^2 - ({}+1)^2 + ({} + 2)^2", sum, sum, sum, sum);
+            let expression = PolyParser::parse(Rule::expression, &program).unwrap();
+            let ids: Vec<usize> = expression.flatten()
+                .filter(|pair| pair.as_rule() == Rule::parenth)
+                .map(|pair| nodes.id(&pair))
+                .collect();
+            assert_eq!(ids.len(), 4);
+            assert_eq!(ids[0], ids[1]);
+            assert_ne!(ids[0], ids[2]);
+            assert_ne!(ids[0], ids[3]);
+            assert_ne!(ids[2], ids[3]);
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting () {
+        let mut polynomial = String::from("x");
+        for i in 1..=20 {
+            polynomial = format!("({} + {}) x", polynomial, i);
+        }
+        let single = poly2r1cs(polynomial.clone(), 0).unwrap();
+        assert_eq!(single.size(), 3 * 20 + 1);
+        // The second copy shares all the nodes of the first, so only the sum of the two is added.
+        let double = poly2r1cs(format!("{} + {}", polynomial, polynomial), 0).unwrap();
+        assert_eq!(double.size(), single.size() + 2);
+    }
+}
@@ -0,0 +1,326 @@
+//! This module implements univariate polynomials over a field, as used by the reduction of
+//! R1CS systems to quadratic arithmetic programs (see the module `qap`).
+//!
+//! A polynomial `p(x) = p_0 + p_1 x + ... + p_d x^d` is stored as its coefficients `p_0, ..., p_d`,
+//! starting with the constant term. The coefficients never end with zeros, so that two polynomials
+//! are equal exactly when their coefficients are, and the zero polynomial has no coefficients.
+//!
+//! Polynomials are multiplied either by multiplying each pair of coefficients (`mul_naive`), or using
+//! the NTT of the module `ntt` (`mul_ntt`). The operator `*` uses the NTT when both polynomials have
+//! at least `NTT_THRESHOLD` coefficients and the field has the roots of unity needed.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::field::{Bn254, Field};
+use crate::ntt::{inverse_ntt, ntt};
+
+/// The number of coefficients from which polynomials are multiplied using the NTT. Below it,
+/// multiplying each pair of coefficients is faster (see `cargo bench --bench polynomial`).
+const NTT_THRESHOLD: usize = 64;
+
+/// A polynomial in one variable `x` with coefficients in the field `F`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnivariatePolynomial<F: Field = Bn254> {
+    coefficients: Vec<F>,
+}
+
+impl<F: Field> Default for UnivariatePolynomial<F> {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl<F: Field> UnivariatePolynomial<F> {
+    /// Creates the polynomial `Σ c_k x^k` from its coefficients `c_k`, starting with the constant term.
+    pub fn new(mut coefficients: Vec<F>) -> Self {
+        while coefficients.last().is_some_and(|c| c.is_zero()) {
+            coefficients.pop();
+        }
+        UnivariatePolynomial { coefficients }
+    }
+
+    /// Returns the zero polynomial.
+    pub fn zero() -> Self {
+        UnivariatePolynomial { coefficients: Vec::new() }
+    }
+
+    /// Returns the constant polynomial `s`.
+    pub fn constant(s: F) -> Self {
+        Self::new(vec![s])
+    }
+
+    /// Returns the monomial `s x^k`.
+    pub fn monomial(s: F, k: usize) -> Self {
+        let mut coefficients = vec![F::zero(); k + 1];
+        coefficients[k] = s;
+        Self::new(coefficients)
+    }
+
+    /// Returns the polynomial `Π (x - x_r)` of degree `n` which vanishes exactly at the `n` given points.
+    pub fn vanishing(points: &[F]) -> Self {
+        points.iter().fold(Self::constant(F::one()), |product, x_r| product * Self::new(vec![-*x_r, F::one()]))
+    }
+
+    /// Returns the unique polynomial `p` of degree less than `n` with `p(x_r) = y_r` for the `n` points
+    /// `x_r` and values `y_r`, using Lagrange interpolation. An error is returned if two points are equal.
+    ///
+    /// The polynomial is `Σ y_r L_r(x)`, where the Lagrange basis polynomial `L_r` is `1` at `x_r` and `0`
+    /// at the other points (see `lagrange_basis`).
+    pub fn interpolate(points: &[F], values: &[F]) -> Result<Self, String> {
+        assert_eq!(points.len(), values.len(), "The number of points and values differ");
+        let basis = Self::lagrange_basis(points)?;
+        Ok(basis.into_iter().zip(values).fold(Self::zero(), |sum, (l_r, y_r)| sum + l_r * *y_r))
+    }
+
+    /// Returns the Lagrange basis polynomials `L_r` of the points `x_r`, where `L_r(x_r) = 1` and
+    /// `L_r(x_s) = 0` for the other points `x_s`. An error is returned if two points are equal.
+    ///
+    /// Given the vanishing polynomial `Z` of the points, `L_r` is `Z(x) / (x - x_r)` divided by its
+    /// value at `x_r`, which takes `O(n)` operations for each of the `n` polynomials.
+    pub fn lagrange_basis(points: &[F]) -> Result<Vec<Self>, String> {
+        let z = Self::vanishing(points);
+        points.iter().enumerate().map(|(r, x_r)| {
+            let l_r = z.divide_by_linear(*x_r);
+            let scale = l_r.evaluate(*x_r).inverse().ok_or(format!("The point {} is repeated (at position {})", x_r, r))?;
+            Ok(l_r * scale)
+        }).collect()
+    }
+
+    /// Returns the coefficients of the polynomial, starting with the constant term.
+    pub fn coefficients(&self) -> &[F] {
+        &self.coefficients
+    }
+
+    /// Returns the degree of the polynomial, or `None` for the zero polynomial.
+    pub fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    /// Checks whether the polynomial is the zero polynomial.
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+
+    /// Evaluates the polynomial at `x` using Horner's method.
+    pub fn evaluate(&self, x: F) -> F {
+        self.coefficients.iter().rev().fold(F::zero(), |value, c| value * x + *c)
+    }
+
+    /// Divides the polynomial by `divisor`, returning the quotient `q` and the remainder `r` such that
+    /// `self = q * divisor + r`, where `r` has lower degree than the divisor.
+    ///
+    /// # Panics
+    /// Panics if the divisor is the zero polynomial.
+    pub fn divrem(&self, divisor: &Self) -> (Self, Self) {
+        let divisor_degree = divisor.degree().expect("Division by the zero polynomial");
+        let leading_inverse = divisor.coefficients[divisor_degree].inverse().unwrap();
+        let mut remainder = self.coefficients.clone();
+        if remainder.len() <= divisor_degree {
+            return (Self::zero(), self.clone());
+        }
+        let mut quotient = vec![F::zero(); remainder.len() - divisor_degree];
+        for k in (0..quotient.len()).rev() {
+            let q_k = remainder[k + divisor_degree] * leading_inverse;
+            quotient[k] = q_k;
+            for (j, d_j) in divisor.coefficients.iter().enumerate() {
+                remainder[k + j] = remainder[k + j] - q_k * *d_j;
+            }
+        }
+        remainder.truncate(divisor_degree);
+        (Self::new(quotient), Self::new(remainder))
+    }
+
+    /// Divides the polynomial by `x^n - 1`, returning the quotient and the remainder just as `divrem`.
+    /// Since `x^k = x^{k-n} (x^n - 1) + x^{k-n}`, this takes `O(d)` operations for a polynomial of degree `d`,
+    /// rather than the `O(d n)` of `divrem`.
+    ///
+    /// # Panics
+    /// Panics if `n` is zero, since `x^0 - 1` is the zero polynomial.
+    pub fn divrem_vanishing(&self, n: usize) -> (Self, Self) {
+        assert!(n > 0, "Division by the zero polynomial");
+        let mut remainder = self.coefficients.clone();
+        if remainder.len() <= n {
+            return (Self::zero(), self.clone());
+        }
+        let mut quotient = vec![F::zero(); remainder.len() - n];
+        for k in (n..remainder.len()).rev() {
+            quotient[k - n] = remainder[k];
+            remainder[k - n] = remainder[k - n] + remainder[k];
+        }
+        remainder.truncate(n);
+        (Self::new(quotient), Self::new(remainder))
+    }
+
+    /// Multiplies two polynomials by multiplying each pair of coefficients, using `O(n m)` operations
+    /// for polynomials with `n` and `m` coefficients.
+    pub fn mul_naive(&self, other: &Self) -> Self {
+        if self.is_zero() || other.is_zero() {
+            return Self::zero();
+        }
+        let mut coefficients = vec![F::zero(); self.coefficients.len() + other.coefficients.len() - 1];
+        for (j, c) in self.coefficients.iter().enumerate() {
+            for (k, d) in other.coefficients.iter().enumerate() {
+                coefficients[j + k] = coefficients[j + k] + *c * *d;
+            }
+        }
+        Self::new(coefficients)
+    }
+
+    /// Multiplies two polynomials using the NTT, in `O(N log N)` operations where `N` is the smallest power of
+    /// two larger than the degree of the product. Both polynomials are evaluated at the `N` powers of a root of
+    /// unity of order `N`, and the product is interpolated from the products of the values. An error is
+    /// returned if the field has no root of unity of order `N`.
+    pub fn mul_ntt(&self, other: &Self) -> Result<Self, String> {
+        if self.is_zero() || other.is_zero() {
+            return Ok(Self::zero());
+        }
+        let size = (self.coefficients.len() + other.coefficients.len() - 1).next_power_of_two();
+        let mut values = self.coefficients.clone();
+        values.resize(size, F::zero());
+        let mut other_values = other.coefficients.clone();
+        other_values.resize(size, F::zero());
+        ntt(&mut values)?;
+        ntt(&mut other_values)?;
+        for (value, other_value) in values.iter_mut().zip(other_values) {
+            *value = *value * other_value;
+        }
+        inverse_ntt(&mut values)?;
+        Ok(Self::new(values))
+    }
+
+    /// Divides the polynomial by `x - a` using synthetic division, dropping the remainder `p(a)`.
+    fn divide_by_linear(&self, a: F) -> Self {
+        let mut quotient = vec![F::zero(); self.coefficients.len().saturating_sub(1)];
+        let mut carry = F::zero();
+        for k in (0..quotient.len()).rev() {
+            carry = self.coefficients[k + 1] + carry * a;
+            quotient[k] = carry;
+        }
+        Self::new(quotient)
+    }
+}
+
+impl<F: Field> Add for UnivariatePolynomial<F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (mut longer, shorter) = if self.coefficients.len() >= other.coefficients.len() { (self, other) } else { (other, self) };
+        for (c, d) in longer.coefficients.iter_mut().zip(shorter.coefficients) {
+            *c = *c + d;
+        }
+        Self::new(longer.coefficients)
+    }
+}
+
+impl<F: Field> Sub for UnivariatePolynomial<F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl<F: Field> Neg for UnivariatePolynomial<F> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -F::one()
+    }
+}
+
+impl<F: Field> Mul<F> for UnivariatePolynomial<F> {
+    type Output = Self;
+
+    /// Multiplies each coefficient of the polynomial by the constant `s`.
+    fn mul(self, s: F) -> Self {
+        Self::new(self.coefficients.into_iter().map(|c| c * s).collect())
+    }
+}
+
+impl<F: Field> Mul for UnivariatePolynomial<F> {
+    type Output = Self;
+
+    /// Multiplies two polynomials, using the NTT for large polynomials when the field allows it.
+    fn mul(self, other: Self) -> Self {
+        if self.coefficients.len().min(other.coefficients.len()) >= NTT_THRESHOLD {
+            if let Ok(product) = self.mul_ntt(&other) {
+                return product;
+            }
+        }
+        self.mul_naive(&other)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a polynomial from integer coefficients, starting with the constant term.
+    fn polynomial(coefficients: &[i64]) -> UnivariatePolynomial<Bn254> {
+        UnivariatePolynomial::new(coefficients.iter().map(|c| Bn254::from(*c)).collect())
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let p = polynomial(&[1, 2, 3]);
+        let q = polynomial(&[-1, 0, -3]);
+        assert_eq!(p.clone() + q.clone(), polynomial(&[0, 2]));
+        assert_eq!((p.clone() + q.clone()).degree(), Some(1));
+        assert_eq!(p.clone() - p.clone(), UnivariatePolynomial::zero());
+        assert_eq!(p.clone() * q.clone(), polynomial(&[-1, -2, -6, -6, -9]));
+        assert_eq!(p.clone() * Bn254::from(2i64), polynomial(&[2, 4, 6]));
+        assert_eq!(p.evaluate(Bn254::from(2i64)), Bn254::from(17i64));
+        assert_eq!(UnivariatePolynomial::<Bn254>::zero().degree(), None);
+        assert_eq!(UnivariatePolynomial::monomial(Bn254::from(3i64), 2), polynomial(&[0, 0, 3]));
+        assert_eq!(polynomial(&[4, 0, 0]).coefficients(), &[Bn254::from(4i64)]);
+    }
+
+    #[test]
+    fn test_ntt_multiplication() {
+        let p = UnivariatePolynomial::<Bn254>::new((0..100i64).map(|k| Bn254::from(k * k - 7)).collect());
+        let q = UnivariatePolynomial::<Bn254>::new((0..70i64).map(|k| Bn254::from(3 - k)).collect());
+        let product = p.mul_naive(&q);
+        assert_eq!(product.degree(), Some(168));
+        assert_eq!(p.mul_ntt(&q).unwrap(), product);
+        assert_eq!(p.clone() * q.clone(), product);
+        assert_eq!(polynomial(&[1, 1]).mul_ntt(&polynomial(&[-1, 1])).unwrap(), polynomial(&[-1, 0, 1]));
+        assert_eq!(p.mul_ntt(&UnivariatePolynomial::zero()).unwrap(), UnivariatePolynomial::zero());
+    }
+
+    #[test]
+    fn test_divrem() {
+        let p = polynomial(&[5, -3, 0, 2, 1]);
+        let d = polynomial(&[1, 0, 2]);
+        let (q, r) = p.divrem(&d);
+        assert!(r.degree() < d.degree());
+        assert_eq!(q * d.clone() + r, p);
+        let (q, r) = polynomial(&[-6, 11, -6, 1]).divrem(&polynomial(&[-1, 1]));
+        assert_eq!((q, r), (polynomial(&[6, -5, 1]), UnivariatePolynomial::zero()));
+        let (q, r) = polynomial(&[1, 1]).divrem(&d);
+        assert_eq!((q, r), (UnivariatePolynomial::zero(), polynomial(&[1, 1])));
+        for n in [1, 2, 3, 5, 8] {
+            let vanishing = UnivariatePolynomial::monomial(Bn254::one(), n) - UnivariatePolynomial::constant(Bn254::one());
+            assert_eq!(p.divrem_vanishing(n), p.divrem(&vanishing));
+        }
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let p = polynomial(&[7, -1, 0, 4]);
+        let points: Vec<Bn254> = [0i64, 1, -2, 5].iter().map(|x| Bn254::from(*x)).collect();
+        let values: Vec<Bn254> = points.iter().map(|x| p.evaluate(*x)).collect();
+        assert_eq!(UnivariatePolynomial::interpolate(&points, &values).unwrap(), p);
+        let z = UnivariatePolynomial::vanishing(&points);
+        assert_eq!(z.degree(), Some(4));
+        assert!(points.iter().all(|x| z.evaluate(*x).is_zero()));
+        let basis = UnivariatePolynomial::lagrange_basis(&points).unwrap();
+        for (r, l_r) in basis.iter().enumerate() {
+            for (s, x_s) in points.iter().enumerate() {
+                assert_eq!(l_r.evaluate(*x_s), if r == s { Bn254::one() } else { Bn254::zero() });
+            }
+        }
+        let repeated = [Bn254::from(1i64), Bn254::from(2i64), Bn254::from(1i64)];
+        assert!(UnivariatePolynomial::interpolate(&repeated, &repeated).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! This module implements the reduction of R1CS systems to quadratic arithmetic programs (QAPs),
+//! which is the first step of SNARKs such as Groth16.
+//!
+//! Each constraint `r` of the system is assigned a distinct point `x_r` of the field. For each
+//! variable `u_i`, the column of `A` is interpolated into the polynomial `A_i(x)` of degree less
+//! than the number of points, where `A_i(x_r)` is the coefficient of `u_i` in the left factor of
+//! constraint `r`. The polynomials `B_i(x)` and `C_i(x)` are interpolated likewise. Given a witness
+//! `w`, the polynomials
+//!
+//! ```text
+//! A(x) = Σ w_i A_i(x),   B(x) = Σ w_i B_i(x),   C(x) = Σ w_i C_i(x)
+//! ```
+//!
+//! take the values of the three sums of constraint `r` at the point `x_r`. Hence the witness satisfies
+//! constraint `r` exactly when `A(x_r) B(x_r) - C(x_r) = 0`, and it satisfies all the constraints exactly
+//! when `A(x) B(x) - C(x)` vanishes at all the points, i.e. when it is divisible by the *target
+//! polynomial* `Z(x) = Π (x - x_r)`. The witness is thus captured by a single polynomial identity
+//! `A(x) B(x) - C(x) = H(x) Z(x)`, which can be checked at a single random point.
+//!
+//! When the field has a root of unity `ω` of order `2^k`, for the smallest `2^k` which is at least the
+//! number of constraints, the points are the powers `ω^r`. The constraints are then padded with the
+//! trivial constraint `0 * 0 = 0` up to `2^k` points, so that `Z(x) = x^(2^k) - 1`, and the polynomials
+//! are interpolated from their values using the inverse NTT of the module `ntt`. Otherwise, the points are
+//! `1, 2, ..., n` for `n` constraints, and the polynomials are computed using Lagrange interpolation,
+//! which takes time quadratic in the number of constraints.
+
+use crate::field::{Bn254, Field};
+use crate::ntt::{inverse_ntt, ntt};
+use crate::polynomial::UnivariatePolynomial;
+use crate::r1cs::R1CS;
+
+/// A quadratic arithmetic program, given by the polynomials `A_i`, `B_i` and `C_i` of each variable
+/// `u_i` and the target polynomial `Z` (see the module `qap`).
+///
+/// The polynomials `A_i`, `B_i` and `C_i` are stored as their non-zero values at the points, i.e. as
+/// the columns of the matrices of the system, and are only interpolated when needed.
+pub struct QAP<F: Field = Bn254> {
+    /// The point `x_r` of each constraint `r`, including the padding constraints.
+    points: Vec<F>,
+    /// Whether the points are the powers of a root of unity, so that polynomials are interpolated using the inverse NTT.
+    roots_of_unity: bool,
+    /// The non-zero values `A_i(x_r)` of each polynomial `A_i`, as pairs `(r, A_i(x_r))`.
+    a: Vec<Vec<(usize, F)>>,
+    b: Vec<Vec<(usize, F)>>,
+    c: Vec<Vec<(usize, F)>>,
+    target: UnivariatePolynomial<F>,
+}
+
+impl<F: Field> R1CS<F> {
+    /// Reduces the system to a quadratic arithmetic program (see the module `qap`).
+    ///
+    /// This collects the columns of the matrices, which takes `O(n)` operations for `n` non-zero
+    /// coefficients, and computes the target polynomial. Over powers of a root of unity, the target is
+    /// `x^N - 1`. Otherwise, it is the product `Π (x - x_r)`, which takes `O(m^2)` operations for `m`
+    /// constraints. The polynomials `A_i(x)`, `B_i(x)` and `C_i(x)` are interpolated by the methods of
+    /// `QAP` which return them.
+    pub fn to_qap(&self) -> QAP<F> {
+        let n = self.size();
+        let size = n.max(1).next_power_of_two();
+        let root = F::root_of_unity(size.trailing_zeros());
+        let points: Vec<F> = match root {
+            Some(root) => std::iter::successors(Some(F::one()), |x| Some(*x * root)).take(size).collect(),
+            None => (1..=n as u64).map(F::from).collect(),
+        };
+        let mut columns = [(); 3].map(|_| vec![Vec::new(); self.num_variables()]);
+        for (r, constraint) in self.constraints().enumerate() {
+            for (column, terms) in columns.iter_mut().zip([constraint.a(), constraint.b(), constraint.c()]) {
+                for (i, x_i) in terms {
+                    column[*i].push((r, *x_i));
+                }
+            }
+        }
+        let target = match root {
+            // The powers of a root of order N are exactly the roots of x^N - 1.
+            Some(_) => UnivariatePolynomial::monomial(F::one(), size) - UnivariatePolynomial::constant(F::one()),
+            None => UnivariatePolynomial::vanishing(&points),
+        };
+        let [a, b, c] = columns;
+        QAP { points, roots_of_unity: root.is_some(), a, b, c, target }
+    }
+}
+
+impl<F: Field> QAP<F> {
+    /// Returns the points `x_r` assigned to the constraints, including the padding constraints.
+    pub fn points(&self) -> &[F] {
+        &self.points
+    }
+
+    /// Returns the number of variables, i.e. the length of a witness.
+    pub fn num_variables(&self) -> usize {
+        self.a.len()
+    }
+
+    /// Returns the polynomial `A_i(x)` interpolating the coefficients of `u_i` in the left factors.
+    pub fn a(&self, i: usize) -> UnivariatePolynomial<F> {
+        self.interpolate_column(&self.a[i])
+    }
+
+    /// Returns the polynomial `B_i(x)` interpolating the coefficients of `u_i` in the right factors.
+    pub fn b(&self, i: usize) -> UnivariatePolynomial<F> {
+        self.interpolate_column(&self.b[i])
+    }
+
+    /// Returns the polynomial `C_i(x)` interpolating the coefficients of `u_i` in the right hand sides.
+    pub fn c(&self, i: usize) -> UnivariatePolynomial<F> {
+        self.interpolate_column(&self.c[i])
+    }
+
+    /// Returns the target polynomial `Z(x) = Π (x - x_r)`, which vanishes exactly at the points.
+    pub fn target(&self) -> &UnivariatePolynomial<F> {
+        &self.target
+    }
+
+    /// Returns the polynomials `A(x) = Σ w_i A_i(x)`, `B(x) = Σ w_i B_i(x)` and `C(x) = Σ w_i C_i(x)` of the witness `w`.
+    ///
+    /// The values `A(x_r)` are the sums of the constraints, so each polynomial is interpolated once from them.
+    ///
+    /// # Panics
+    /// Panics if the length of the witness differs from the number of variables.
+    pub fn witness_polynomials(&self, witness: &[F]) -> (UnivariatePolynomial<F>, UnivariatePolynomial<F>, UnivariatePolynomial<F>) {
+        assert_eq!(witness.len(), self.num_variables(), "The witness has the wrong length");
+        let combine = |columns: &[Vec<(usize, F)>]| {
+            let mut values = vec![F::zero(); self.points.len()];
+            for (column, w_i) in columns.iter().zip(witness) {
+                for (r, x_r) in column {
+                    values[*r] = values[*r] + *x_r * *w_i;
+                }
+            }
+            self.interpolate(values)
+        };
+        (combine(&self.a), combine(&self.b), combine(&self.c))
+    }
+
+    /// Returns the polynomial with the given non-zero values `(r, y_r)` at the points `x_r`, and zero at the other points.
+    fn interpolate_column(&self, column: &[(usize, F)]) -> UnivariatePolynomial<F> {
+        let mut values = vec![F::zero(); self.points.len()];
+        for (r, y_r) in column {
+            values[*r] = *y_r;
+        }
+        self.interpolate(values)
+    }
+
+    /// Returns the polynomial of degree less than the number of points which takes the given values at the points.
+    /// This takes `O(N log N)` operations for `N` powers of a root of unity, using the inverse NTT, and
+    /// `O(n^2)` operations otherwise, using Lagrange interpolation.
+    fn interpolate(&self, mut values: Vec<F>) -> UnivariatePolynomial<F> {
+        if self.roots_of_unity {
+            inverse_ntt(&mut values).expect("The number of points is the order of a root of unity");
+            UnivariatePolynomial::new(values)
+        } else {
+            UnivariatePolynomial::interpolate(&self.points, &values).expect("The points are distinct")
+        }
+    }
+
+    /// Returns the quotient `H(x) = (A(x) B(x) - C(x)) / Z(x)` for the witness `w`. An error is returned
+    /// if the division leaves a remainder, i.e. if the witness does not satisfy some constraint. The
+    /// error then tells the first constraint `r` for which `A(x_r) B(x_r) - C(x_r)` is not zero.
+    ///
+    /// When the points are the `N` powers of a root of unity, the product is computed using the NTT and
+    /// the division by `Z(x) = x^N - 1` takes `O(N)` operations, so that this takes `O(N log N)` operations.
+    pub fn quotient(&self, witness: &[F]) -> Result<UnivariatePolynomial<F>, String> {
+        if witness.len() != self.num_variables() {
+            return Err(format!("The witness has {} values, but the system has {} variables", witness.len(), self.num_variables()));
+        }
+        let (a, b, c) = self.witness_polynomials(witness);
+        let difference = a * b - c;
+        let (quotient, remainder) = if self.roots_of_unity {
+            difference.divrem_vanishing(self.points.len())
+        } else {
+            difference.divrem(&self.target)
+        };
+        if !remainder.is_zero() {
+            // The remainder takes the same values as the difference at the points.
+            let values = if self.roots_of_unity {
+                let mut values = remainder.coefficients().to_vec();
+                values.resize(self.points.len(), F::zero());
+                ntt(&mut values).expect("The number of points is the order of a root of unity");
+                values
+            } else {
+                self.points.iter().map(|x_r| remainder.evaluate(*x_r)).collect()
+            };
+            let r = values.iter().position(|value| !value.is_zero()).unwrap();
+            return Err(format!("A(x)B(x) - C(x) is not divisible by Z(x), since it is not zero at the point {} of constraint {}", self.points[r], r));
+        }
+        Ok(quotient)
+    }
+
+    /// Checks whether the witness satisfies the program, i.e. whether `w_0 = 1` and `A(x) B(x) - C(x)`
+    /// is divisible by `Z(x)`. This holds exactly when the witness satisfies the R1CS system.
+    pub fn is_satisfied(&self, witness: &[F]) -> bool {
+        witness.first() == Some(&F::one()) && self.quotient(witness).is_ok()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::builder::ConstraintSystem;
+    use crate::field::{FieldParameters, Fp};
+    use crate::parser::equation2r1cs;
+
+    /// The parameters of the field modulo the prime `2^61 - 1`, which has no roots of unity of order above 2.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    struct Mersenne61Parameters;
+
+    impl FieldParameters for Mersenne61Parameters {
+        const MODULUS: [u64; 4] = [(1 << 61) - 1, 0, 0, 0];
+        // Since p = 3 mod 4, -1 is not a square.
+        const NON_RESIDUE: u64 = (1 << 61) - 2;
+    }
+
+    type Mersenne61 = Fp<Mersenne61Parameters>;
+
+    #[test]
+    fn test_qap_of_satisfying_witness() {
+        let r1cs = equation2r1cs("let t = x*y + 3; t^3 - x != 5; bits(y, 4) = 9".to_string()).unwrap();
+        let qap = r1cs.to_qap();
+        let size = r1cs.size().next_power_of_two();
+        assert_eq!(qap.points().len(), size);
+        assert_eq!(qap.points()[1].pow(size as u64), Bn254::one());
+        assert_eq!(qap.target().clone(), UnivariatePolynomial::monomial(Bn254::one(), size) - UnivariatePolynomial::constant(Bn254::one()));
+        for (r, constraint) in r1cs.constraints().enumerate() {
+            let x_r = qap.points()[r];
+            for (i, x_i) in constraint.b() {
+                assert_eq!(qap.b(*i).evaluate(x_r), *x_i);
+            }
+        }
+
+        let inputs = HashMap::from([("x".to_string(), Bn254::from(2i64)), ("y".to_string(), Bn254::from(9i64))]);
+        let witness = r1cs.generate_witness(&inputs).unwrap();
+        let h = qap.quotient(&witness).unwrap();
+        let (a, b, c) = qap.witness_polynomials(&witness);
+        let a_sum = witness.iter().enumerate().fold(UnivariatePolynomial::zero(), |sum, (i, w_i)| sum + qap.a(i) * *w_i);
+        assert_eq!(a, a_sum);
+        assert_eq!(a * b - c, h.clone() * qap.target().clone());
+        assert!(h.degree() <= Some(size - 2));
+        assert!(qap.is_satisfied(&witness));
+
+        let mut wrong = witness.clone();
+        let t = r1cs.get_variable_index("t").unwrap();
+        wrong[t] = wrong[t] + Bn254::one();
+        let r = r1cs.first_unsatisfied_constraint(&wrong).unwrap();
+        let error = qap.quotient(&wrong).err().unwrap();
+        assert!(error.ends_with(&format!("constraint {}", r)), "Unexpected error: {}", error);
+        assert!(!qap.is_satisfied(&wrong));
+        assert!(qap.quotient(&witness[1..]).is_err());
+    }
+
+    #[test]
+    fn test_qap_without_roots_of_unity() {
+        let cs = ConstraintSystem::<Mersenne61>::new();
+        let x = cs.input("x");
+        cs.assert_equal(x.clone() * x.clone() * x + 1, Mersenne61::from(28u64));
+        let r1cs = cs.into_r1cs();
+        assert_eq!(r1cs.size(), 5);
+        let qap = r1cs.to_qap();
+        assert_eq!(qap.points(), &[1u64, 2, 3, 4, 5].map(Mersenne61::from));
+        let witness = r1cs.generate_witness(&HashMap::from([("x".to_string(), Mersenne61::from(3u64))])).unwrap();
+        assert!(qap.is_satisfied(&witness));
+        let mut wrong = witness.clone();
+        wrong[2] = wrong[2] + Mersenne61::one();
+        let r = r1cs.first_unsatisfied_constraint(&wrong).unwrap();
+        assert!(qap.quotient(&wrong).err().unwrap().ends_with(&format!("constraint {}", r)));
+        let witness = r1cs.generate_witness(&HashMap::from([("x".to_string(), Mersenne61::from(4u64))]));
+        assert!(witness.is_err());
+    }
+}
@@ -3,7 +3,8 @@
 //! We can think of these as collections of equations on the form 
 //! `Σ a_i u_i * Σ b_j u_j = Σ c_k u_k` where `u_i`, `u_j`, and `u_k` are variables.
 //! 
-//! In this implementation, the R1CS is represented as a collection of such equations.
+//! In this implementation, the R1CS is represented as a collection of such equations, where
+//! the sums are stored in the flat matrices `A`, `B` and `C` (see the module `sparse_matrix`).
 //! 
 //! Along with the equations, we also give a mapping from variable names to their indices.
 //! This is not strictly necessary for a R1CS, since a variable can be identified by its
@@ -11,37 +12,66 @@
 //! named variables and keeping track of their indices. A highly optimized implementation
 //! of R1CS should probably not contain this feature.
 //! 
-//! At the moment, this module is not very useful, since there is no way to access the 
-//! constraints after adding them to the system (except in the test functions). This should
-//! obviously be changed when we start using the module somewhere.
+//! Some variables cannot be computed by simply evaluating the constraints (e.g. the inverse
+//! of a value). For these, the system stores *hints* telling how to compute them from the other
+//! variables. Hints are only used when generating a witness (see the module `witness`); they
+//! are not part of the constraint system itself.
+//!
+//! The system is deterministic: the names are kept in the order in which they were added, and
+//! the sums of the constraints are ordered by variable index, so that compiling the same
+//! polynomial twice gives identical systems, which are also written identically in the textual
+//! format of the module `text_format`.
 
+use indexmap::IndexMap;
+#[cfg(test)]
 use std::collections::HashMap;
-use crate::r1cs_constraint::R1CSConstraint;
+use crate::field::{Bn254, Field};
+use crate::parallel;
+use crate::r1cs_constraint::{ConstraintRef, R1CSConstraint};
+use crate::sparse_matrix::SparseMatrix;
+use crate::witness::Hint;
 
 /// This struct represents a Rank-1 Constraint System (R1CS) constraint.
 /// 
-/// It has a list of constraints given as the type `R1CSConstraint`, and a mapping
-/// from variable names to their indices.
-pub struct R1CS {
-    variables: HashMap<String, usize>,
-    constraints: Vec<R1CSConstraint>,
+/// It has a list of constraints, added as the type `R1CSConstraint` and stored as the rows of
+/// the matrices `a`, `b` and `c` (so that constraint `r` is `a[r] * b[r] = c[r]`), and a mapping
+/// from variable names to their indices, ordered by when the names were added. The coefficients of the constraints are
+/// elements of the field `F`, which defaults to the scalar field of BN254.
+pub struct R1CS<F: Field = Bn254> {
+    variables: IndexMap<String, usize>,
+    /// Names given to internal variables, e.g. by let-bindings. These are not inputs.
+    named_variables: IndexMap<String, usize>,
+    a: SparseMatrix<F>,
+    b: SparseMatrix<F>,
+    c: SparseMatrix<F>,
+    hints: Vec<(usize, Hint<F>)>,
     /// The next index to be used for a new variable.
     next_index: usize,
 }
 
-impl R1CS {
+impl<F: Field> Default for R1CS<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> R1CS<F> {
     /// Create a new, empty R1CS system.
     pub fn new() -> Self {
         R1CS {
-            variables: HashMap::new(),
-            constraints: Vec::new(),
+            variables: IndexMap::new(),
+            named_variables: IndexMap::new(),
+            a: SparseMatrix::new(),
+            b: SparseMatrix::new(),
+            c: SparseMatrix::new(),
+            hints: Vec::new(),
             next_index: 1,
         }
     }
 
     /// Returns the size, i.e. the number of constraints in the system.
     pub fn size(&self) -> usize {
-        self.constraints.len()
+        self.a.num_rows()
     }
 
     /// Returns the input size, i.e. the number of variables in the system.
@@ -49,9 +79,28 @@ impl R1CS {
         self.variables.len()
     }
 
+    /// Returns the total number of variables in the system, including `u_0` and
+    /// all internal variables. This is the length of a witness for the system.
+    pub fn num_variables(&self) -> usize {
+        self.next_index
+    }
+
     /// Adds a constraint to the system.
-    pub fn add_constraint(&mut self, constraint: R1CSConstraint) {
-        self.constraints.push(constraint);
+    pub fn add_constraint(&mut self, constraint: R1CSConstraint<F>) {
+        self.a.push_row(constraint.a().terms());
+        self.b.push_row(constraint.b().terms());
+        self.c.push_row(constraint.c().terms());
+    }
+
+    /// Returns the number of non-zero coefficients in all the constraints of the system.
+    pub fn num_coefficients(&self) -> usize {
+        self.a.num_entries() + self.b.num_entries() + self.c.num_entries()
+    }
+
+    /// Adds a hint to the system, telling witness generation how to compute the
+    /// variable `u_i` when it cannot be computed from the constraints.
+    pub fn add_hint(&mut self, i: usize, hint: Hint<F>) {
+        self.hints.push((i, hint));
     }
 
     /// Adds a new explicit variable to the system, i.e. a variable
@@ -71,39 +120,124 @@ impl R1CS {
         current_index
     }
 
-    /// Get the index of a variable by its name.
+    /// Gives a name to the internal variable `u_i`, so that its index can be found by
+    /// `get_variable_index`. Unlike input variables, named internal variables are computed
+    /// during witness generation, and should not be given as inputs.
+    pub fn name_variable(&mut self, name: String, i: usize) {
+        self.named_variables.insert(name, i);
+    }
+
+    /// Get the index of a variable by its name. The variable can be either an
+    /// input variable or a named internal variable.
     pub fn get_variable_index(&self, name: &str) -> Option<usize> {
-        self.variables.get(name).copied()
+        self.variables.get(name).or_else(|| self.named_variables.get(name)).copied()
     }
 
-}
+    /// Returns the input variables as pairs of their names and indices, in the order in which they were added.
+    pub fn inputs(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.variables.iter().map(|(name, position)| (name.as_str(), *position))
+    }
 
+    /// Returns the mapping from input variable names to their indices.
+    pub(crate) fn variables(&self) -> &IndexMap<String, usize> {
+        &self.variables
+    }
 
-#[cfg(test)]
-impl R1CS {
-    
-    /// Prints the R1CS system in a human-readable format.
-    /// The system is printed as a list of the constraints.
-    /// Used for testing and debugging purposes.
-    pub fn print(&self) {
-        println!("=== R1CS ===\n");
-        for (variable_name, variable_position) in self.variables.iter() {
-            println!("u_{} = {}", variable_position, variable_name);
+    /// Returns constraint number `r` of the system.
+    pub(crate) fn constraint(&self, r: usize) -> ConstraintRef<'_, F> {
+        ConstraintRef::new(self.a.row(r), self.b.row(r), self.c.row(r))
+    }
+
+    /// Removes the constraints from the system and returns them, keeping the variables and hints.
+    /// This is used when the constraints are passed on as they are created (see the module `streaming`).
+    pub(crate) fn take_constraints(&mut self) -> Vec<R1CSConstraint<F>> {
+        let constraints = (0..self.size()).map(|r| R1CSConstraint::new(
+            self.a.row(r).iter().copied().collect(),
+            self.b.row(r).iter().copied().collect(),
+            self.c.row(r).iter().copied().collect(),
+        )).collect();
+        self.a.clear();
+        self.b.clear();
+        self.c.clear();
+        constraints
+    }
+
+    /// Returns the constraints of the system, in the order in which they were added.
+    pub(crate) fn constraints(&self) -> impl ExactSizeIterator<Item = ConstraintRef<'_, F>> + '_ {
+        (0..self.size()).map(|r| self.constraint(r))
+    }
+
+    /// Returns the products `A w`, `B w` and `C w` of the matrices of the system and a witness `w`,
+    /// i.e. the values of the three sums of each constraint. The witness satisfies the system
+    /// exactly when `A w ∘ B w = C w`. With the feature `parallel`, the products are computed in parallel.
+    pub fn matrix_products(&self, witness: &[F]) -> (Vec<F>, Vec<F>, Vec<F>) {
+        (self.a.multiply(witness), self.b.multiply(witness), self.c.multiply(witness))
+    }
+
+    /// Returns the index of the first constraint which is not satisfied by the witness, if any.
+    /// With the feature `parallel`, the constraints are checked in parallel.
+    pub(crate) fn first_unsatisfied_constraint(&self, witness: &[F]) -> Option<usize> {
+        parallel::position(0..self.size(), |r| !self.constraint(r).is_satisfied(witness))
+    }
+
+    /// Returns the hints of the system, each together with the variable it computes.
+    pub(crate) fn hints(&self) -> &[(usize, Hint<F>)] {
+        &self.hints
+    }
+
+    /// Returns the mapping from the names of internal variables to their indices.
+    pub(crate) fn named_variables(&self) -> &IndexMap<String, usize> {
+        &self.named_variables
+    }
+
+    /// Adds the input variable `name` as the variable `u_i`, which need not be the next variable.
+    /// This is used when reading a system in the textual format (see the module `text_format`).
+    pub(crate) fn add_input_variable_at(&mut self, name: String, i: usize) {
+        self.variables.insert(name, i);
+        self.next_index = self.next_index.max(i + 1);
+    }
+
+    /// Extends the system to `n` variables (including `u_0`), if it has fewer.
+    pub(crate) fn reserve_variables(&mut self, n: usize) {
+        self.next_index = self.next_index.max(n);
+    }
+
+    /// Returns the prefix used when writing the variables by their indices, as in `u_3`. This is
+    /// `u_`, unless some variable name starts with `u_`. In that case, underscores are added to the
+    /// prefix until no variable name starts with it, so that the names never collide.
+    pub(crate) fn index_prefix(&self) -> String {
+        let mut prefix = "u_".to_string();
+        while self.variables.keys().chain(self.named_variables.keys()).any(|name| name.starts_with(&prefix)) {
+            prefix.push('_');
         }
-        for constraint in self.constraints.iter() {
-            constraint.print();
+        prefix
+    }
+
+    /// Returns the names used when writing the variables, i.e. `names[i]` is the name of `u_i`.
+    /// Named variables are written using their names, and the remaining variables by their indices.
+    pub(crate) fn variable_names(&self) -> Vec<String> {
+        let prefix = self.index_prefix();
+        let mut names: Vec<String> = (0..self.next_index).map(|i| format!("{}{}", prefix, i)).collect();
+        // Input names take precedence over let-bound names of the same variable.
+        for (name, position) in self.named_variables.iter().chain(self.variables.iter()) {
+            names[*position] = name.clone();
         }
+        names
     }
+}
+
+
+#[cfg(test)]
+impl<F: Field> R1CS<F> {
 
     /// Given two hashmaps, `expected_a` and `expected_b`, and an optional hashmap `expected_c`,
     /// finds and returns a constraint in the system that matches the expected form.
     /// Used for testing and debugging purposes.
-    pub fn find_matching_constraint(&self, expected_a: &HashMap<usize, i64>, expected_b: &HashMap<usize, i64>, expected_c: Option<&HashMap<usize, i64>>,) -> Option<&R1CSConstraint> {
-        self.constraints.iter().find(|constraint| {
-            if expected_c.is_none() {
-                constraint.lhs_matches(expected_a, expected_b)
-            } else {
-                constraint.lhs_matches(expected_a, expected_b) && constraint.rhs_matches(expected_c.unwrap())
+    pub(crate) fn find_matching_constraint(&self, expected_a: &HashMap<usize, i64>, expected_b: &HashMap<usize, i64>, expected_c: Option<&HashMap<usize, i64>>,) -> Option<ConstraintRef<'_, F>> {
+        self.constraints().find(|constraint| {
+            match expected_c {
+                None => constraint.lhs_matches(expected_a, expected_b),
+                Some(expected_c) => constraint.lhs_matches(expected_a, expected_b) && constraint.rhs_matches(expected_c),
             }
         })
     }
@@ -11,204 +11,297 @@
 //! numbers `a_i`, `b_i`, and `c_i` respectively.
 //! We do not consider the variables explicitly, but consider them to be identified by a unique index, i.e. 
 //! the variables `u_1`, `u_2`, `u_3`, etc. are identified by `1`, `2`, `3`, etc.
-//! Since the constraints are assumed to be sparse, we represent each sum as a hashmap, where the key represents the
-//! variable (by the associated index) and the value represents the coefficient of that variable.
+//! Since the constraints are assumed to be sparse, we represent each sum as a `LinearCombination` (see the
+//! module `linear_combination`), which stores the coefficients of the variables appearing in the sum.
 //! 
-//! At the moment, this module is not very useful, since there is no way to access the 
-//! innards of the constraint after creating it (except in the test functions). This should
-//! obviously be changed when we start using the module somewhere.
+//! The coefficients are elements of some finite field `F` (see the module `field`).
+//! Inside the crate, the constraints can be evaluated on a witness, which is how witness
+//! generation checks and solves them.
 
 
+use std::collections::BTreeMap;
+#[cfg(test)]
 use std::collections::HashMap;
+use crate::field::Field;
+use crate::linear_combination::{self, LinearCombination};
 
 /// This struct represents a Rank-1 Constraint System (R1CS) constraint.
 /// 
 /// For a constraint on the form `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i`
 /// the value of `a[i]` is the coefficient of `u_i` in the first sum,
 /// `b[i]` is the coefficient of `u_i` in the second sum, and 
-/// `c[i]` is the coefficient of `u_i` in the third sum. 
-pub struct R1CSConstraint {
-    a: HashMap<usize, i64>,
-    b: HashMap<usize, i64>,
-    c: HashMap<usize, i64>,
+/// `c[i]` is the coefficient of `u_i` in the third sum.
+#[derive(PartialEq)]
+pub struct R1CSConstraint<F: Field> {
+    a: LinearCombination<F>,
+    b: LinearCombination<F>,
+    c: LinearCombination<F>,
 }
 
-
-/// This struct represents a Rank-1 Constraint System (R1CS) constraint.
-/// 
-/// This struct is used to build special types of R1CS constraints, more specifically
-/// constraints which forms a simple sum of other variables.
-/// 
-/// For a constraint on the form `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i`
-/// the value of `a[i]` is the coefficient of `u_i` in the first sum,
-/// `b[i]` is the coefficient of `u_i` in the second sum, and 
-/// `c[i]` is the coefficient of `u_i` in the third sum. 
-pub struct R1CSSumConstraint {
-    a: HashMap<usize, i64>,
-    b: HashMap<usize, i64>,
-    c: HashMap<usize, i64>,
+/// A borrowed R1CS constraint, where each sum is given by its terms `(i, x_i)` sorted by `i`.
+/// This is how the constraints are read from the flat storage of a R1CS system.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ConstraintRef<'a, F: Field> {
+    a: &'a [(usize, F)],
+    b: &'a [(usize, F)],
+    c: &'a [(usize, F)],
 }
 
 
-impl R1CSConstraint {
+impl<F: Field> R1CSConstraint<F> {
+    /// Creates the R1CS constraint `a * b = c` from three arbitrary linear combinations.
+    pub fn new(a: LinearCombination<F>, b: LinearCombination<F>, c: LinearCombination<F>) -> Self {
+        R1CSConstraint { a, b, c }
+    }
+
+    /// Creates a new R1CS constraint representing a linear combination. I.e. if `Σ x_i u_i` is
+    /// a linear combination, and `u_k` is a variable, we represent the constraint `Σ x_i u_i = u_k`.
+    pub fn new_linear_combination_constraint(sum: LinearCombination<F>, k: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::constant(F::one()),
+            b: sum,
+            c: LinearCombination::variable(k),
+        }
+    }
+
     /// Creates a new R1CS constraint representing multiplication of two variables.
     /// More precicely, we use this if we have variables `u_i`, `u_j`, and `u_k`, and want to 
     /// represent the constraint `u_i * u_j = u_k`, 
     pub fn new_multiplication_constraint(i: usize, j: usize, k: usize) -> Self {
         R1CSConstraint {
-            a: HashMap::from([(i, 1)]),
-            b: HashMap::from([(j, 1)]),
-            c: HashMap::from([(k, 1)]),
+            a: LinearCombination::from([(i, F::one())]),
+            b: LinearCombination::from([(j, F::one())]),
+            c: LinearCombination::from([(k, F::one())]),
         }
     }
 
     /// Creates a new R1CS constraint representing multiplication of a variable and a constant.
     /// More precicely, we use this if we have variables `u_j`, and `u_k`, the constant `s` and want to
     /// represent the constraint `s * u_j = u_k`.
-    pub fn new_constant_multiplication_constraint(s: i64, j: usize, k: usize) -> Self {
+    pub fn new_constant_multiplication_constraint(s: F, j: usize, k: usize) -> Self {
         R1CSConstraint {
-            a: HashMap::from([(0,s)]),
-            b: HashMap::from([(j, 1)]),
-            c: HashMap::from([(k, 1)]),
+            a: LinearCombination::from([(0,s)]),
+            b: LinearCombination::from([(j, F::one())]),
+            c: LinearCombination::from([(k, F::one())]),
         }
     }
 
     /// Creates a new R1CS constraint representing the "result" of the R1CS system. I.e.
     /// the constraint that `u_j = s` for some constant `s`.
-    pub fn new_final_constraint(s : i64, j : usize) -> Self {
+    pub fn new_final_constraint(s : F, j : usize) -> Self {
         R1CSConstraint {
-            a: HashMap::from([(0,1)]),
-            b: HashMap::from([(j, 1)]),
-            c: HashMap::from([(0, s)]),
+            a: LinearCombination::from([(0,F::one())]),
+            b: LinearCombination::from([(j, F::one())]),
+            c: LinearCombination::from([(0, s)]),
         }
     }
 
     /// Creates a new R1CS constraint representing a constant. I.e. if `s` is a constant,
     /// and `u_k` is a variable, we want to represent the constraint `s = u_k`.
-    pub fn new_constant_constraint(s: i64, k: usize) -> Self {
+    pub fn new_constant_constraint(s: F, k: usize) -> Self {
         R1CSConstraint {
-            a: HashMap::from([(0, 1)]),
-            b: HashMap::from([(0, s)]),
-            c: HashMap::from([(k, 1)]),
+            a: LinearCombination::from([(0, F::one())]),
+            b: LinearCombination::from([(0, s)]),
+            c: LinearCombination::from([(k, F::one())]),
         }
     }
-}
 
+    /// Creates a new R1CS constraint stating that `u_j - s` is non-zero, given a variable `u_k`
+    /// which should hold the inverse of `u_j - s`. The constraint is `(u_j - s) * u_k = 1`, which
+    /// can only be satisfied if `u_j - s` has an inverse, i.e. if `u_j != s`.
+    pub fn new_inverse_constraint(s: F, j: usize, k: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::from([(j, F::one()), (0, -s)]),
+            b: LinearCombination::from([(k, F::one())]),
+            c: LinearCombination::from([(0, F::one())]),
+        }
+    }
 
-impl R1CSSumConstraint {
-
-    /// Creates a new R1CSSumConstraint representing a sum of variables.
-    /// At the beginning, this is the empty constraint `1 * 0 = 0`.
-    pub fn new() -> Self {
-        R1CSSumConstraint {
-            a: HashMap::from([(0,1)]),
-            b: HashMap::new(),
-            c: HashMap::new(),
+    /// Creates a new R1CS constraint stating that the variable `u_i` is boolean, i.e. either
+    /// `0` or `1`. The constraint is `u_i * (1 - u_i) = 0`.
+    pub fn new_boolean_constraint(i: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::from([(i, F::one())]),
+            b: LinearCombination::from([(0, F::one()), (i, -F::one())]),
+            c: LinearCombination::new(),
         }
     }
 
+    /// Creates a new R1CS constraint stating that the variable `u_k` is the number with
+    /// the binary representation given by the variables `bits` (least significant bit first).
+    /// I.e., if `bits` is `[i_0, i_1, ..., i_n]`, the constraint is `Σ 2^j u_{i_j} = u_k`.
+    pub fn new_bit_decomposition_constraint(bits: &[usize], k: usize) -> Self {
+        let mut power_of_two = F::one();
+        let mut b = LinearCombination::new();
+        for i in bits.iter() {
+            b.add_term(*i, power_of_two);
+            power_of_two = power_of_two + power_of_two;
+        }
+        R1CSConstraint {
+            a: LinearCombination::from([(0, F::one())]),
+            b,
+            c: LinearCombination::from([(k, F::one())]),
+        }
+    }
 
-    /// Adds a variable to the left hand side of the constraint.
-    /// More precicely, if we already represent the constraint `Σ u_j = rhs`
-    /// for some sum and some right hand side `rhs`, then after this call we have `Σ u_j + u_i = rhs`.
-    /// 
-    /// **Note**: This forces us to create intermediate constraints, which may not be the
-    /// actual constraints we want to represent. 
-    pub fn add_to_sum(&mut self, i : usize) {
-        self.b.insert(i, 1);
+    /// Creates a new R1CS constraint representing a difference of two variables shifted by a constant.
+    /// More precicely, we use this if we have variables `u_i`, `u_j`, and `u_k`, the constant `s` and want to
+    /// represent the constraint `u_i - u_j + s = u_k`.
+    pub fn new_shifted_difference_constraint(s: F, i: usize, j: usize, k: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::from([(0, F::one())]),
+            b: LinearCombination::from([(i, F::one()), (j, -F::one()), (0, s)]),
+            c: LinearCombination::from([(k, F::one())]),
+        }
     }
 
-    /// Adds a variable to the left hand side of the constraint.
-    /// More precicely, if we already represent the constraint `Σ u_j = rhs`
-    /// for some sum and some right hand side `rhs`, then after this call we have `Σ u_j - u_i = rhs`.
-    /// 
-    /// **Note**: This forces us to create intermediate constraints, which may not be the
-    /// actual constraints we want to represent. 
-    pub fn subtract_from_sum(&mut self, position : usize) {
-        self.b.insert(position, -1);
+    /// Creates a new R1CS constraint representing a selection between two variables.
+    /// More precicely, if `u_b` is boolean, we use this to represent that `u_k` equals
+    /// `u_i` if `u_b = 1` and `u_j` if `u_b = 0`. The constraint is `u_b * (u_i - u_j) = u_k - u_j`.
+    pub fn new_selection_constraint(b: usize, i: usize, j: usize, k: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::from([(b, F::one())]),
+            b: LinearCombination::from([(i, F::one()), (j, -F::one())]),
+            c: LinearCombination::from([(k, F::one()), (j, -F::one())]),
+        }
     }
-    
-    /// Sets the right hand side of the constraint.
-    /// More precicely, if we already represent the constraint `Σ u_j = 0`,
-    /// then after this call we have `Σ u_j = s`.
-    /// 
-    /// **Note**: This forces us to create intermediate constraints, which may not be the
-    /// actual constraints we want to represent. 
-    pub fn set_right_hand_side(&mut self, s : usize) {
-        self.c.insert(s, 1);
+
+    /// Creates a new R1CS constraint representing the disjunction `u_k = u_i or u_j` of two boolean
+    /// variables `u_i` and `u_j`. Since `u_i or u_j = u_i + u_j - u_i * u_j` for boolean values,
+    /// the constraint is `u_i * u_j = u_i + u_j - u_k`.
+    pub fn new_disjunction_constraint(i: usize, j: usize, k: usize) -> Self {
+        R1CSConstraint {
+            a: LinearCombination::from([(i, F::one())]),
+            b: LinearCombination::from([(j, F::one())]),
+            c: LinearCombination::from([(i, F::one()), (j, F::one()), (k, -F::one())]),
+        }
     }
 
-    /// Transforms an object of type `R1CSSumConstraint` to an object of type `R1CSConstraint`.
-    pub fn to_r1cs_constraint(self) -> R1CSConstraint {
+    /// Creates a new R1CS constraint representing the exclusive disjunction `u_k = u_i xor u_j` of two
+    /// boolean variables `u_i` and `u_j`. Since `u_i xor u_j = u_i + u_j - 2 u_i * u_j` for boolean values,
+    /// the constraint is `2u_i * u_j = u_i + u_j - u_k`.
+    pub fn new_exclusive_disjunction_constraint(i: usize, j: usize, k: usize) -> Self {
         R1CSConstraint {
-            a: self.a,
-            b: self.b,
-            c: self.c,
+            a: LinearCombination::from([(i, F::from(2u64))]),
+            b: LinearCombination::from([(j, F::one())]),
+            c: LinearCombination::from([(i, F::one()), (j, F::one()), (k, -F::one())]),
+        }
+    }
+
+    /// Returns the first sum `Σ a_i u_i` of the constraint.
+    pub fn a(&self) -> &LinearCombination<F> {
+        &self.a
+    }
+
+    /// Returns the second sum `Σ b_i u_i` of the constraint.
+    pub fn b(&self) -> &LinearCombination<F> {
+        &self.b
+    }
+
+    /// Returns the third sum `Σ c_i u_i` of the constraint, i.e. the right hand side.
+    pub fn c(&self) -> &LinearCombination<F> {
+        &self.c
+    }
+
+    /// Returns a view of the constraint, borrowing its sums. Used for testing purposes.
+    #[cfg(test)]
+    pub(crate) fn view(&self) -> ConstraintRef<'_, F> {
+        ConstraintRef::new(self.a.terms(), self.b.terms(), self.c.terms())
+    }
+}
+
+impl<'a, F: Field> ConstraintRef<'a, F> {
+    /// Creates a view of the constraint `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i` from the terms of the sums.
+    pub(crate) fn new(a: &'a [(usize, F)], b: &'a [(usize, F)], c: &'a [(usize, F)]) -> Self {
+        ConstraintRef { a, b, c }
+    }
+
+    /// Returns the terms `(i, a_i)` of the left factor `Σ a_i u_i`, sorted by `i`.
+    pub(crate) fn a(&self) -> &'a [(usize, F)] {
+        self.a
+    }
+
+    /// Returns the terms `(i, b_i)` of the right factor `Σ b_i u_i`, sorted by `i`.
+    pub(crate) fn b(&self) -> &'a [(usize, F)] {
+        self.b
+    }
+
+    /// Returns the terms `(i, c_i)` of the right hand side `Σ c_i u_i`, sorted by `i`.
+    pub(crate) fn c(&self) -> &'a [(usize, F)] {
+        self.c
+    }
+
+    /// Returns the variables appearing in the constraint (including `u_0`), each
+    /// together with a flag telling whether it appears only in the right hand side.
+    pub(crate) fn variables(&self) -> BTreeMap<usize, bool> {
+        let mut variables = BTreeMap::new();
+        for (position, _) in self.c.iter() {
+            variables.insert(*position, true);
+        }
+        for (position, _) in self.a.iter().chain(self.b.iter()) {
+            variables.insert(*position, false);
         }
+        variables
+    }
+
+    /// Returns the coefficient of `u_i` in the right hand side of the constraint.
+    pub(crate) fn rhs_coefficient(&self, i: usize) -> F {
+        linear_combination::coefficient(self.c, i)
+    }
+
+    /// Evaluates the three sums of the constraint given the values of the variables.
+    /// The result is the triple `(Σ a_i u_i, Σ b_i u_i, Σ c_i u_i)`.
+    pub(crate) fn evaluate(&self, values: &[F]) -> (F, F, F) {
+        (linear_combination::evaluate(self.a, values), linear_combination::evaluate(self.b, values), linear_combination::evaluate(self.c, values))
+    }
+
+    /// Checks whether the constraint is satisfied by the given values of the variables.
+    pub(crate) fn is_satisfied(&self, values: &[F]) -> bool {
+        let (a, b, c) = self.evaluate(values);
+        a * b == c
+    }
+
+    /// Evaluates the three sums of the constraint for a batch of `n` assignments, stored column
+    /// by column (see `linear_combination::evaluate_batch`). Each sum has one value per assignment.
+    pub(crate) fn evaluate_batch(&self, values: &[F], n: usize) -> (Vec<F>, Vec<F>, Vec<F>) {
+        (linear_combination::evaluate_batch(self.a, values, n), linear_combination::evaluate_batch(self.b, values, n), linear_combination::evaluate_batch(self.c, values, n))
     }
-    
 }
 
+
 #[cfg(test)]
-impl R1CSConstraint {
+impl<F: Field> ConstraintRef<'_, F> {
+    /// Converts a hashmap with integer coefficients to a linear combination.
+    fn to_linear_combination(x: &HashMap<usize,i64>) -> LinearCombination<F> {
+        x.iter().map(|(position, value)| (*position, F::from(*value))).collect()
+    }
+
+    /// Checks if the sum given by `terms` equals the hashmap with integer coefficients.
+    fn sum_matches(terms: &[(usize, F)], other: &HashMap<usize,i64>) -> bool {
+        terms == Self::to_linear_combination(other).terms()
+    }
+
     /// Checks if a constraint has the expected form of its `a` and `b` vectors. This method is used
     /// only for testing and debugging purposes.
     pub fn lhs_matches(&self, other_a: &HashMap<usize,i64>, other_b: &HashMap<usize,i64>) -> bool {
-        self.a == *other_a && self.b == *other_b
+        Self::sum_matches(self.a, other_a) && Self::sum_matches(self.b, other_b)
     }
 
     /// Checks if a constraint has the expected form of its `c` vector. This method is used
     /// only for testing and debugging purposes.
     pub fn rhs_matches(&self, other_c: &HashMap<usize,i64>) -> bool {
-        self.c == *other_c
+        Self::sum_matches(self.c, other_c)
     }
 
-    /// Returns the `c` vector of the constraint as a vector of pairs.
+    /// Returns the values of the `b` vector of the constraint, ordered by the index of the variable.
     /// Used for testing and debugging purposes.
-    pub fn get_c_pairs (&self) -> Vec<(usize, i64)> {
-        let mut pairs = vec![];
-        for (position, value) in self.c.iter() {
-            pairs.push((*position, *value));
-        }
-        pairs
-    }
-
-
-    /// Presents the hashmap as a string of the form `Σ a_i u_i`
-    /// Used in debugging.
-    fn hashmap_str(x: &HashMap<usize,i64>) -> String {
-        let mut values = vec![];
-        for (position, value) in x.iter() {
-            if *position == 0 {
-                values.push(value.to_string());
-            } else {
-                if *value == 1 {
-                    values.push(format!("u_{}", position));
-                } else {
-                    values.push(format!("{}u_{}", value, position));
-                }
-            }
-        }
-        values.join(" + ")
-    }
-
-    /// Prints the constraint on the form `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i`
-    /// Used in debugging.
-    pub fn print(&self) {
-        let a_string = Self::hashmap_str(&self.a);
-        let b_string = Self::hashmap_str(&self.b);
-        let c_string = Self::hashmap_str(&self.c);
-        if a_string == "1" {
-            println!("{} = {}", b_string, c_string);
-        } else {
-            println!("({})*({}) = {}", a_string, b_string, c_string)
-        }
+    pub fn get_b_values(&self) -> Vec<F> {
+        self.b.iter().map(|(_, value)| *value).collect()
     }
 
-
-
-
+    /// Returns the `c` vector of the constraint as a vector of pairs.
+    /// Used for testing and debugging purposes.
+    pub fn get_c_pairs (&self) -> Vec<(usize, F)> {
+        self.c.to_vec()
+    }
 }
-
-
-
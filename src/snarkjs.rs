@@ -0,0 +1,320 @@
+//! This module implements serialization of R1CS systems in the JSON format written by
+//! `snarkjs r1cs export json`, using serde. It is enabled by the feature `serde`.
+//!
+//! In this format, the variables are called *wires*, and are ordered as `u_0 = 1`, the outputs,
+//! the public inputs, the private inputs, and then the internal variables. Our systems have neither
+//! outputs nor public inputs, so all the input variables are written as private inputs, in the order
+//! in which they were added, and the remaining variables follow in the order of their indices. The
+//! list `map` gives the index `i` of the variable `u_i` of each wire. The constraints are written as
+//! triples of objects mapping wires to coefficients, as in
+//!
+//! ```text
+//! {
+//!   "n8": 32,
+//!   "prime": "21888242871839275222246405745257275088548364400416034343698204186575808495617",
+//!   "nVars": 4,
+//!   "nOutputs": 0,
+//!   "nPubInputs": 0,
+//!   "nPrvInputs": 2,
+//!   "nLabels": 4,
+//!   "nConstraints": 1,
+//!   "useCustomGates": false,
+//!   "constraints": [[{"1": "1"}, {"2": "1"}, {"3": "1"}]],
+//!   "map": [0, 1, 2, 3],
+//!   "customGates": [],
+//!   "customGatesUses": []
+//! }
+//! ```
+//!
+//! The names of the variables and the hints are not part of the format. When a system is deserialized,
+//! its variables are numbered by their wires, and the inputs are named `public[k]` and `private[k]`.
+//! Variables which are not defined by a constraint (e.g. inverses) can then not be computed when
+//! generating a witness.
+
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+use crate::linear_combination::LinearCombination;
+use crate::parser::parse_constant;
+use crate::r1cs::R1CS;
+use crate::r1cs_constraint::R1CSConstraint;
+
+/// A R1CS system in the format of snarkjs. The field names are those written by snarkjs, where the
+/// labels are the signals of the circom circuit, which we identify with the wires.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", bound = "")]
+struct SnarkjsR1CS<F: Field> {
+    /// The number of bytes of an element of the field.
+    n8: usize,
+    /// The modulus of the field, in decimal.
+    prime: String,
+    n_vars: usize,
+    n_outputs: usize,
+    n_pub_inputs: usize,
+    n_prv_inputs: usize,
+    #[serde(default)]
+    n_labels: usize,
+    #[serde(default)]
+    n_constraints: usize,
+    #[serde(default)]
+    use_custom_gates: bool,
+    constraints: Vec<[Sum<F>; 3]>,
+    /// The label of each wire, i.e. the index of the variable of the wire in our system.
+    #[serde(default)]
+    map: Vec<usize>,
+    #[serde(default)]
+    custom_gates: Vec<CustomGate>,
+    #[serde(default)]
+    custom_gates_uses: Vec<CustomGate>,
+}
+
+/// A sum `Σ x_w u_w` of a constraint, written as an object mapping the wires `w` to the coefficients
+/// `x_w` in decimal, ordered by the wires.
+struct Sum<F: Field>(Vec<(usize, F)>);
+
+/// An entry of `customGates` or `customGatesUses`, which are used by PLONK circuits. Custom gates are
+/// not supported, so these lists are always empty, and cannot be deserialized unless they are empty.
+struct CustomGate;
+
+impl<F: Field> Serialize for Sum<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (wire, value) in &self.0 {
+            map.serialize_entry(&wire.to_string(), &value.to_string())?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, F: Field> Deserialize<'de> for Sum<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// Reads the entries of a sum one by one.
+        struct SumVisitor<F: Field>(std::marker::PhantomData<F>);
+
+        impl<'de, F: Field> Visitor<'de> for SumVisitor<F> {
+            type Value = Sum<F>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an object mapping wires to coefficients in decimal")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Sum<F>, A::Error> {
+                let mut terms = Vec::new();
+                while let Some((wire, value)) = map.next_entry::<String, String>()? {
+                    let wire = wire.parse().map_err(|_| de::Error::custom(format!("Invalid wire `{}`", wire)))?;
+                    if value.is_empty() || !value.bytes().all(|digit| digit.is_ascii_digit()) {
+                        return Err(de::Error::custom(format!("Invalid coefficient `{}` of wire {}", value, wire)));
+                    }
+                    terms.push((wire, parse_constant(&value)));
+                }
+                Ok(Sum(terms))
+            }
+        }
+
+        deserializer.deserialize_map(SumVisitor(std::marker::PhantomData))
+    }
+}
+
+impl Serialize for CustomGate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomGate {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(de::Error::custom("Custom gates are not supported"))
+    }
+}
+
+/// Returns the modulus of the field in decimal, i.e. one more than the element `-1`.
+fn modulus<F: Field>() -> String {
+    let mut digits = (-F::one()).to_string().into_bytes();
+    for digit in digits.iter_mut().rev() {
+        if *digit < b'9' {
+            *digit += 1;
+            return String::from_utf8(digits).unwrap();
+        }
+        *digit = b'0';
+    }
+    format!("1{}", String::from_utf8(digits).unwrap())
+}
+
+impl<F: Field> R1CS<F> {
+    /// Returns the order of the wires in the format of snarkjs, i.e. the index `i` of the variable
+    /// `u_i` of each wire: first `u_0`, then the inputs in the order in which they were added, and
+    /// then the remaining variables in the order of their indices.
+    ///
+    /// A witness `w` of the system is thus given to snarkjs as the values `w[i]` in this order.
+    pub fn wire_order(&self) -> Vec<usize> {
+        let mut is_input = vec![false; self.num_variables()];
+        let mut order = vec![0];
+        for (_, i) in self.inputs() {
+            is_input[i] = true;
+            order.push(i);
+        }
+        order.extend((1..self.num_variables()).filter(|i| !is_input[*i]));
+        order
+    }
+}
+
+impl<F: Field> From<&R1CS<F>> for SnarkjsR1CS<F> {
+    fn from(r1cs: &R1CS<F>) -> Self {
+        let map = r1cs.wire_order();
+        let mut wires = vec![0; map.len()];
+        for (wire, i) in map.iter().enumerate() {
+            wires[*i] = wire;
+        }
+        let sum = |terms: &[(usize, F)]| {
+            let mut terms: Vec<(usize, F)> = terms.iter().map(|(i, x_i)| (wires[*i], *x_i)).collect();
+            terms.sort_by_key(|(wire, _)| *wire);
+            Sum(terms)
+        };
+        SnarkjsR1CS {
+            n8: F::modulus_bit_length().div_ceil(64) * 8,
+            prime: modulus::<F>(),
+            n_vars: r1cs.num_variables(),
+            n_outputs: 0,
+            n_pub_inputs: 0,
+            n_prv_inputs: r1cs.input_size(),
+            n_labels: r1cs.num_variables(),
+            n_constraints: r1cs.size(),
+            use_custom_gates: false,
+            constraints: r1cs.constraints().map(|constraint| [sum(constraint.a()), sum(constraint.b()), sum(constraint.c())]).collect(),
+            map,
+            custom_gates: Vec::new(),
+            custom_gates_uses: Vec::new(),
+        }
+    }
+}
+
+impl<F: Field> TryFrom<SnarkjsR1CS<F>> for R1CS<F> {
+    type Error = String;
+
+    fn try_from(system: SnarkjsR1CS<F>) -> Result<Self, String> {
+        if system.prime != modulus::<F>() {
+            return Err(format!("The system is defined over the field of order {}, rather than {}", system.prime, modulus::<F>()));
+        }
+        if system.use_custom_gates {
+            return Err("Custom gates are not supported".to_string());
+        }
+        let first_input = system.n_outputs + 1;
+        if first_input + system.n_pub_inputs + system.n_prv_inputs > system.n_vars {
+            return Err(format!("The system has {} variables, which is too few for its inputs and outputs", system.n_vars));
+        }
+        let mut r1cs = R1CS::new();
+        for k in 0..system.n_pub_inputs {
+            r1cs.add_input_variable_at(format!("public[{}]", k), first_input + k);
+        }
+        for k in 0..system.n_prv_inputs {
+            r1cs.add_input_variable_at(format!("private[{}]", k), first_input + system.n_pub_inputs + k);
+        }
+        for (r, constraint) in system.constraints.into_iter().enumerate() {
+            let [a, b, c] = constraint.map(|Sum(terms)| terms);
+            if let Some((wire, _)) = a.iter().chain(&b).chain(&c).find(|(wire, _)| *wire >= system.n_vars) {
+                return Err(format!("Constraint {} uses the wire {}, but the system has only {} variables", r, wire, system.n_vars));
+            }
+            let sum = |terms: Vec<(usize, F)>| terms.into_iter().collect::<LinearCombination<F>>();
+            r1cs.add_constraint(R1CSConstraint::new(sum(a), sum(b), sum(c)));
+        }
+        r1cs.reserve_variables(system.n_vars);
+        Ok(r1cs)
+    }
+}
+
+/// Serializes the system in the format of `snarkjs r1cs export json`.
+impl<F: Field> Serialize for R1CS<F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SnarkjsR1CS::from(self).serialize(serializer)
+    }
+}
+
+/// Deserializes a system in the format of `snarkjs r1cs export json`, numbering the variables by their wires.
+impl<'de, F: Field> Deserialize<'de> for R1CS<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        R1CS::try_from(SnarkjsR1CS::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use serde_json::json;
+    use crate::field::Bn254;
+    use crate::parser::equation2r1cs;
+
+    #[test]
+    fn test_snarkjs_format() {
+        let r1cs = equation2r1cs("let t = x*x; t*y - x = 6".to_string()).unwrap();
+        let minus_one = (-Bn254::one()).to_string();
+        let expected = json!({
+            "n8": 32,
+            "prime": "21888242871839275222246405745257275088548364400416034343698204186575808495617",
+            "nVars": 6,
+            "nOutputs": 0,
+            "nPubInputs": 0,
+            "nPrvInputs": 2,
+            "nLabels": 6,
+            "nConstraints": 4,
+            "useCustomGates": false,
+            "constraints": [
+                [{"1": "1"}, {"1": "1"}, {"3": "1"}],
+                [{"3": "1"}, {"2": "1"}, {"4": "1"}],
+                [{"0": "1"}, {"1": minus_one, "4": "1"}, {"5": "1"}],
+                [{"0": "1"}, {"5": "1"}, {"0": "6"}],
+            ],
+            "map": [0, 1, 3, 2, 4, 5],
+            "customGates": [],
+            "customGatesUses": [],
+        });
+        assert_eq!(serde_json::to_value(&r1cs).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_deserialized_system() {
+        let r1cs = equation2r1cs("let t = z*y + x; sum(i in 0..3, w[i] * t^i) = 7".to_string()).unwrap();
+        let json = serde_json::to_string(&r1cs).unwrap();
+        let read: R1CS = serde_json::from_str(&json).unwrap();
+        assert_eq!(read.num_variables(), r1cs.num_variables());
+        assert_eq!(read.size(), r1cs.size());
+        assert_eq!(read.wire_order(), (0..r1cs.num_variables()).collect::<Vec<_>>());
+        let value = serde_json::to_value(&read).unwrap();
+        assert_eq!(value["constraints"], serde_json::from_str::<serde_json::Value>(&json).unwrap()["constraints"]);
+
+        // z = 1, y = 1, x = 0 and w = [1, 2, 4], so that t = 1 and the sum is 7.
+        let values = [1, 1, 0, 1, 2, 4].map(|value| Bn254::from(value as u64));
+        let inputs: HashMap<String, Bn254> = r1cs.inputs().zip(values).map(|((name, _), value)| (name.to_string(), value)).collect();
+        let witness = r1cs.generate_witness(&inputs).unwrap();
+        let renamed: HashMap<String, Bn254> = (0..values.len()).map(|k| (format!("private[{}]", k), values[k])).collect();
+        let expected: Vec<Bn254> = r1cs.wire_order().iter().map(|i| witness[*i]).collect();
+        assert_eq!(read.generate_witness(&renamed).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_deserialization_errors() {
+        let system = |prime: &str, constraints: serde_json::Value| json!({
+            "n8": 32, "prime": prime, "nVars": 3, "nOutputs": 0, "nPubInputs": 1, "nPrvInputs": 1,
+            "constraints": constraints,
+        });
+        let prime = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        let read: R1CS = serde_json::from_value(system(prime, json!([[{"1": "1"}, {"2": "1"}, {"0": "6"}]]))).unwrap();
+        assert_eq!(read.inputs().collect::<Vec<_>>(), vec![("public[0]", 1), ("private[0]", 2)]);
+        let cases = [
+            (system("7", json!([])), "field of order 7"),
+            (system(prime, json!([[{"3": "1"}, {}, {}]])), "uses the wire 3"),
+            (system(prime, json!([[{"1": "-1"}, {}, {}]])), "Invalid coefficient `-1`"),
+            (system(prime, json!([[{"x": "1"}, {}, {}]])), "Invalid wire `x`"),
+        ];
+        for (value, expected) in cases {
+            let error = serde_json::from_value::<R1CS>(value).err().unwrap().to_string();
+            assert!(error.contains(expected), "Unexpected error: {}", error);
+        }
+        let mut value = system(prime, json!([]));
+        value["customGates"] = json!([{"templateName": "CMul"}]);
+        assert!(serde_json::from_value::<R1CS>(value).err().unwrap().to_string().contains("Custom gates"));
+    }
+}
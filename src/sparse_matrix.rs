@@ -0,0 +1,96 @@
+//! This module implements the flat storage of the matrices `A`, `B` and `C` of a R1CS system.
+//!
+//! Row `r` of a matrix holds the sum of constraint number `r`, e.g. row `r` of `A` is the sum
+//! `Σ a_i u_i` of the constraint `Σ a_i u_i * Σ b_j u_j = Σ c_k u_k`. The matrices are very sparse:
+//! a row typically has one to three non-zero entries, out of possibly millions of columns.
+//!
+//! We therefore store each matrix in the *compressed sparse row* (CSR) format: the non-zero entries
+//! of all rows are kept in a single vector, where the entries of each row are sorted by column,
+//! and a second vector holds the position at which each row starts. Compared to storing a
+//! separate map per row, this needs no allocation per constraint, and no space beyond the entries
+//! themselves and one offset per row.
+
+use crate::field::Field;
+use crate::linear_combination;
+use crate::parallel;
+
+/// A sparse matrix in the compressed sparse row format, to which rows can only be appended.
+///
+/// The entries of row `r` are `entries[row_starts[r]..row_starts[r + 1]]`, given as pairs
+/// `(i, x_i)` of a column and a non-zero value, sorted by column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SparseMatrix<F: Field> {
+    row_starts: Vec<usize>,
+    entries: Vec<(usize, F)>,
+}
+
+impl<F: Field> Default for SparseMatrix<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> SparseMatrix<F> {
+    /// Creates a new matrix without any rows.
+    pub(crate) fn new() -> Self {
+        SparseMatrix {
+            row_starts: vec![0],
+            entries: Vec::new(),
+        }
+    }
+
+    /// Appends a row, given by its entries `(i, x_i)` sorted by the column `i`.
+    pub(crate) fn push_row(&mut self, entries: &[(usize, F)]) {
+        self.entries.extend_from_slice(entries);
+        self.row_starts.push(self.entries.len());
+    }
+
+    /// Returns the entries `(i, x_i)` of row `r`, sorted by the column `i`.
+    pub(crate) fn row(&self, r: usize) -> &[(usize, F)] {
+        &self.entries[self.row_starts[r]..self.row_starts[r + 1]]
+    }
+
+    /// Returns the product `M w` of the matrix `M` and the vector `w`, i.e. the sum `Σ x_i w_i`
+    /// of the entries `(i, x_i)` of each row. With the feature `parallel`, the rows are summed in parallel.
+    pub(crate) fn multiply(&self, w: &[F]) -> Vec<F> {
+        parallel::map(0..self.num_rows(), |r| linear_combination::evaluate(self.row(r), w))
+    }
+
+    /// Removes all rows of the matrix, keeping the allocated memory.
+    pub(crate) fn clear(&mut self) {
+        self.row_starts.truncate(1);
+        self.entries.clear();
+    }
+
+    /// Returns the number of rows of the matrix.
+    pub(crate) fn num_rows(&self) -> usize {
+        self.row_starts.len() - 1
+    }
+
+    /// Returns the total number of non-zero entries of the matrix.
+    pub(crate) fn num_entries(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Bn254;
+
+    #[test]
+    fn test_rows() {
+        let f = |value: i64| Bn254::from(value);
+        let mut matrix = SparseMatrix::new();
+        matrix.push_row(&[(0, f(1)), (3, f(2))]);
+        matrix.push_row(&[]);
+        matrix.push_row(&[(2, f(-1))]);
+        assert_eq!(matrix.num_rows(), 3);
+        assert_eq!(matrix.num_entries(), 3);
+        assert_eq!(matrix.row(0), &[(0, f(1)), (3, f(2))]);
+        assert!(matrix.row(1).is_empty());
+        assert_eq!(matrix.row(2), &[(2, f(-1))]);
+        assert_eq!(matrix.multiply(&[f(1), f(2), f(3), f(4)]), vec![f(9), f(0), f(-3)]);
+    }
+}
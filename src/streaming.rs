@@ -0,0 +1,302 @@
+//! This module implements a streaming front-end, compiling a polynomial read from a `Read`
+//! one term at a time.
+//!
+//! The function `poly2r1cs` needs the whole polynomial as a string, and keeps the parse tree
+//! and every visited node in memory until the system is complete. For polynomials with millions
+//! of terms (typically generated by other tools), this takes a lot of memory. Instead, the
+//! functions in this module split the polynomial into its terms while reading it, and compile
+//! each term on its own, so that only a single term is kept in memory at a time.
+//!
+//! The sum of the terms is split into chunks of `SUM_CHUNK_SIZE` terms, each given a variable
+//! of its own, so that no single constraint grows with the size of the polynomial. Using
+//! `poly2r1cs_streaming`, the constraints are passed to a sink as soon as they are created,
+//! so that the memory used is bounded by the number of variables rather than the number of constraints.
+//!
+//! Note that, unlike `poly2r1cs`, nodes are not shared between different terms. Identical
+//! sub-polynomials in different terms are therefore given different variables.
+
+use std::io::{BufReader, Bytes, Read};
+
+use crate::field::{Bn254, Field};
+use crate::linear_combination::LinearCombination;
+use crate::lowering;
+use crate::parser::term2r1cs;
+use crate::r1cs::R1CS;
+use crate::r1cs_constraint::R1CSConstraint;
+
+/// The number of terms summed by a single constraint.
+const SUM_CHUNK_SIZE: usize = 1024;
+
+/// A term of a polynomial, as split by `TermReader`.
+struct Term {
+    is_negative: bool,
+    text: String,
+    /// The line and column where the term starts.
+    position: (usize, usize),
+}
+
+/// Reads a polynomial from a `Read`, splitting it into its terms.
+///
+/// A term ends at a `+` or `-` outside of any parentheses or brackets, unless the sign follows
+/// a `*`, `^`, `(`, `[` or `,` (as in `x * -y`), in which case it belongs to the term. Comments
+/// are replaced by a space, so that signs inside comments are ignored.
+struct TermReader<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    /// A byte which has been read, but not yet handled.
+    peeked: Option<u8>,
+    /// The line and column of the next byte.
+    position: (usize, usize),
+    /// The number of open parentheses and brackets.
+    depth: usize,
+    /// The last byte read which is neither whitespace nor part of a comment.
+    previous: Option<u8>,
+    /// The sign of the next term, if a sign has been read.
+    next_is_negative: Option<bool>,
+}
+
+impl<R: Read> TermReader<R> {
+    fn new(reader: R) -> Self {
+        TermReader {
+            bytes: BufReader::new(reader).bytes(),
+            peeked: None,
+            position: (1, 1),
+            depth: 0,
+            previous: None,
+            next_is_negative: None,
+        }
+    }
+
+    /// Returns the next byte, updating the position.
+    fn next_byte(&mut self) -> Result<Option<u8>, String> {
+        let byte = match self.peeked.take() {
+            Some(byte) => Some(byte),
+            None => self.bytes.next().transpose().map_err(|e| format!("Error reading polynomial: {}", e))?,
+        };
+        if byte == Some(b'\n') {
+            self.position = (self.position.0 + 1, 1);
+        } else if byte.is_some() {
+            self.position.1 += 1;
+        }
+        Ok(byte)
+    }
+
+    /// Returns the next byte without consuming it.
+    fn peek_byte(&mut self) -> Result<Option<u8>, String> {
+        if self.peeked.is_none() {
+            self.peeked = self.bytes.next().transpose().map_err(|e| format!("Error reading polynomial: {}", e))?;
+        }
+        Ok(self.peeked)
+    }
+
+    /// Skips a comment, given that its first `/` has been read and the next byte is `/` or `*`.
+    fn skip_comment(&mut self) -> Result<(), String> {
+        let is_line_comment = self.next_byte()? == Some(b'/');
+        let mut previous = None;
+        loop {
+            match self.next_byte()? {
+                None => return Ok(()),
+                Some(b'\n') if is_line_comment => return Ok(()),
+                Some(b'/') if !is_line_comment && previous == Some(b'*') => return Ok(()),
+                byte => previous = byte,
+            }
+        }
+    }
+
+    /// Reads the next term, or returns `None` at the end of the polynomial.
+    fn next_term(&mut self) -> Result<Option<Term>, String> {
+        let mut text = Vec::new();
+        let mut position = self.position;
+        loop {
+            let byte_position = self.position;
+            let byte = match self.next_byte()? {
+                Some(byte) => byte,
+                None => break,
+            };
+            match byte {
+                b'/' if matches!(self.peek_byte()?, Some(b'/') | Some(b'*')) => {
+                    self.skip_comment()?;
+                    text.push(b' ');
+                    continue;
+                }
+                b'(' | b'[' => self.depth += 1,
+                b')' | b']' => self.depth = self.depth.saturating_sub(1),
+                b'+' | b'-' if self.depth == 0 && !matches!(self.previous, Some(b'*' | b'^' | b'(' | b'[' | b',')) => {
+                    let is_negative = byte == b'-';
+                    if self.previous.is_none() {
+                        if !is_negative {
+                            return Err(format!("Line {}, column {}: A polynomial cannot start with `+`.", byte_position.0, byte_position.1));
+                        }
+                        self.next_is_negative = Some(true);
+                        self.previous = Some(byte);
+                        continue;
+                    }
+                    if text.iter().all(|byte: &u8| byte.is_ascii_whitespace()) {
+                        return Err(format!("Line {}, column {}: Expected a term before `{}`.", byte_position.0, byte_position.1, byte as char));
+                    }
+                    self.previous = Some(byte);
+                    let term = self.finish_term(text, position)?;
+                    self.next_is_negative = Some(is_negative);
+                    return Ok(Some(term));
+                }
+                _ => {}
+            }
+            if byte.is_ascii_whitespace() {
+                if text.is_empty() {
+                    position = self.position;
+                    continue;
+                }
+            } else {
+                self.previous = Some(byte);
+            }
+            text.push(byte);
+        }
+        if text.iter().all(|byte| byte.is_ascii_whitespace()) {
+            return match self.next_is_negative.take() {
+                Some(_) => Err(format!("Line {}, column {}: Expected a term at the end of the polynomial.", self.position.0, self.position.1)),
+                None => Ok(None),
+            };
+        }
+        self.finish_term(text, position).map(Some)
+    }
+
+    /// Creates a term from its text, using the sign read before the term.
+    fn finish_term(&mut self, text: Vec<u8>, position: (usize, usize)) -> Result<Term, String> {
+        let text = String::from_utf8(text)
+            .map_err(|_| format!("Line {}, column {}: The term is not valid UTF-8.", position.0, position.1))?;
+        Ok(Term {
+            is_negative: self.next_is_negative.take().unwrap_or(false),
+            text,
+            position,
+        })
+    }
+}
+
+/// Compiles the polynomial read from `reader`, with the expected result `expected_result`, one
+/// term at a time. If a sink is given, the constraints are passed to it as they are created.
+fn compile<R: Read, F: Field>(
+    reader: R,
+    expected_result: i64,
+    mut sink: Option<&mut dyn FnMut(R1CSConstraint<F>) -> Result<(), String>>,
+) -> Result<R1CS<F>, String> {
+    let mut terms = TermReader::new(reader);
+    let mut r1cs = R1CS::new();
+    let mut sum = LinearCombination::new();
+    let mut terms_in_sum = 0;
+    let mut should_create_new_variable = false;
+    while let Some(term) = terms.next_term()? {
+        let variable = term2r1cs(&mut r1cs, &term.text)
+            .map_err(|e| format!("Line {}, column {}: {}", term.position.0, term.position.1, e))?;
+        should_create_new_variable |= terms_in_sum > 0 || term.is_negative;
+        sum.add_term(variable, if term.is_negative { -F::one() } else { F::one() });
+        terms_in_sum += 1;
+        if terms_in_sum == SUM_CHUNK_SIZE {
+            let partial_sum = lowering::linear_combination(&mut r1cs, std::mem::take(&mut sum));
+            sum = LinearCombination::variable(partial_sum);
+            terms_in_sum = 1;
+            should_create_new_variable = false;
+        }
+        if let Some(sink) = sink.as_mut() {
+            for constraint in r1cs.take_constraints() {
+                sink(constraint)?;
+            }
+        }
+    }
+    let variable = match sum.as_variable() {
+        _ if terms_in_sum == 0 => return Err("The polynomial is empty.".to_string()),
+        Some(variable) if !should_create_new_variable => variable,
+        _ => lowering::linear_combination(&mut r1cs, sum),
+    };
+    r1cs.add_constraint(R1CSConstraint::new_final_constraint(F::from(expected_result), variable));
+    if let Some(sink) = sink.as_mut() {
+        for constraint in r1cs.take_constraints() {
+            sink(constraint)?;
+        }
+    }
+    Ok(r1cs)
+}
+
+/// Reads a polynomial from `reader` and generates a R1CS system from it, just as `poly2r1cs`.
+///
+/// The polynomial is compiled one term at a time, so that it never needs to be kept in memory.
+/// The resulting system is equivalent to the one given by `poly2r1cs`, but may have more
+/// variables, since nodes are not shared between terms.
+pub fn poly2r1cs_reader<R: Read>(reader: R, expected_result: i64) -> Result<R1CS<Bn254>, String> {
+    compile(reader, expected_result, None)
+}
+
+/// Reads a polynomial from `reader` and generates a R1CS system from it, passing each constraint
+/// to `sink` as soon as it is created. The sink may e.g. write the constraints to a file, and
+/// returns an error to stop the compilation.
+///
+/// The returned system has the input variables, the number of variables and the hints of the
+/// compiled system (which are needed for witness generation), but none of its constraints.
+pub fn poly2r1cs_streaming<R, F, S>(reader: R, expected_result: i64, mut sink: S) -> Result<R1CS<F>, String>
+where
+    R: Read,
+    F: Field,
+    S: FnMut(R1CSConstraint<F>) -> Result<(), String>,
+{
+    compile(reader, expected_result, Some(&mut sink))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::parser::poly2r1cs;
+
+    /// Generates the witness of a system for the inputs `x_i = i + 1`, where `n` is the number of inputs.
+    fn witness_inputs(n: usize) -> HashMap<String, Bn254> {
+        (0..n).map(|i| (format!("x_{}", i), Bn254::from(i as u64 + 1))).collect()
+    }
+
+    #[test]
+    fn test_streaming_matches_poly2r1cs() {
+        let polynomial = "(x + y)^7 - 3x*y /* - 5 */ + bits(z, 4)\n  // -x\n  + x * -(y - 1) - 2";
+        let streamed = poly2r1cs_reader(polynomial.as_bytes(), 10).unwrap();
+        let parsed = poly2r1cs(polynomial.to_string(), 10).unwrap();
+        assert_eq!(streamed.inputs().collect::<Vec<_>>(), parsed.inputs().collect::<Vec<_>>());
+        assert!(streamed.constraints().eq(parsed.constraints()));
+    }
+
+    #[test]
+    fn test_large_polynomial() {
+        // x_0 + 2x_1 + ... + n x_{n - 1} with x_i = i + 1, i.e. the sum of the squares 1, 4, ..., n^2.
+        let n = 3 * SUM_CHUNK_SIZE + 5;
+        let polynomial: Vec<String> = (0..n).map(|i| format!("{}x_{}", i + 1, i)).collect();
+        let expected = (1..=n as i64).map(|i| i * i).sum();
+        let r1cs = poly2r1cs_reader(polynomial.join(" + ").as_bytes(), expected).unwrap();
+        let witness = r1cs.generate_witness(&witness_inputs(n)).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+        // No constraint sums more than a chunk of terms.
+        assert!(r1cs.constraints().all(|constraint| constraint.variables().len() <= SUM_CHUNK_SIZE + 2));
+    }
+
+    #[test]
+    fn test_sink() {
+        let mut constraints = Vec::new();
+        let mut r1cs: R1CS<Bn254> = poly2r1cs_streaming("x_0^2 - x_1 + 4".as_bytes(), 3, |constraint| {
+            constraints.push(constraint);
+            Ok(())
+        }).unwrap();
+        assert_eq!(r1cs.size(), 0);
+        assert_eq!(r1cs.input_size(), 2);
+        for constraint in constraints {
+            r1cs.add_constraint(constraint);
+        }
+        assert!(r1cs.generate_witness(&witness_inputs(2)).is_ok());
+        let error = poly2r1cs_streaming::<_, Bn254, _>("x + y".as_bytes(), 7, |_| Err("Disk full".to_string())).err().unwrap();
+        assert_eq!(error, "Disk full");
+    }
+
+    #[test]
+    fn test_streaming_errors() {
+        let error = poly2r1cs_reader("x +\n  y * (z".as_bytes(), 1).err().unwrap();
+        assert!(error.starts_with("Line 2, column 3:"), "Unexpected error: {}", error);
+        assert!(poly2r1cs_reader("x + + y".as_bytes(), 1).is_err());
+        assert!(poly2r1cs_reader("x -".as_bytes(), 1).is_err());
+        assert!(poly2r1cs_reader("+x".as_bytes(), 1).is_err());
+        assert!(poly2r1cs_reader(" // nothing\n".as_bytes(), 1).is_err());
+    }
+}
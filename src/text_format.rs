@@ -0,0 +1,388 @@
+//! This module implements a human-readable textual format for R1CS systems, which can be written
+//! by hand, e.g. for small examples in teaching and tests.
+//!
+//! A system is written using `Display` (e.g. by `r1cs.to_string()`), and read back using `FromStr`
+//! (e.g. by `text.parse::<R1CS>()`), following the grammar in *r1cs.pest*. The system
+//! `equation2r1cs("let t = x^2; t - 2x != 3")` is written as
+//!
+//! ```text
+//! variables 6
+//! input x = u_1
+//! let t = u_2
+//! hint u_5 = inverse(-3 + u_4)
+//! (x) * (x) = (t)
+//! (2) * (x) = (u_3)
+//! (1) * (t - u_3) = (u_4)
+//! (-3 + u_4) * (u_5) = (1)
+//! ```
+//!
+//! The first line gives the number of variables, including `u_0`. It is followed by the declarations
+//! of the named variables, where the inputs are declared in the order in which their values are given.
+//! Then follow the hints, which tell witness generation how to compute the variables which are not
+//! defined by a constraint (see the module `witness`), and finally one line `(a) * (b) = (c)` per
+//! constraint `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i`.
+//!
+//! Variables are written by their names, or as `u_i` if they have none. The coefficients are written
+//! as the integers of smallest absolute value representing them, so that `-1` is written rather than
+//! `p - 1`. Writing a system and reading it back gives the same system, which is also written identically.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use pest::Parser;
+use pest_derive::Parser;
+
+use crate::field::Field;
+use crate::linear_combination::LinearCombination;
+use crate::parser::parse_constant;
+use crate::r1cs::R1CS;
+use crate::r1cs_constraint::R1CSConstraint;
+use crate::witness::Hint;
+
+#[derive(Parser)]
+#[grammar = "r1cs.pest"]
+/// A struct implementing a parser of the textual format of R1CS systems, based on the
+/// grammar defined in *r1cs.pest*.
+struct R1CSParser;
+
+/// Checks whether `-x` is smaller than `x`, when both are seen as the smallest non-negative
+/// integers representing them. Such an element `x` is written as the negative integer `-(-x)`.
+fn is_negative<F: Field>(x: F) -> bool {
+    let y = -x;
+    let length = x.bit_length();
+    if length != y.bit_length() {
+        return y.bit_length() < length;
+    }
+    (0..length).rev().find(|i| x.bit(*i) != y.bit(*i)).is_some_and(|i| x.bit(i))
+}
+
+/// Writes the sum `Σ x_i u_i` given by its terms, where `u_i` is written as `name(i)`.
+fn sum_to_string<F: Field>(terms: &[(usize, F)], name: impl Fn(usize) -> String) -> String {
+    if terms.is_empty() {
+        return "0".to_string();
+    }
+    let mut sum = String::new();
+    for (position, value) in terms {
+        let (sign, magnitude) = if is_negative(*value) { ("-", -*value) } else { ("+", *value) };
+        sum.push_str(match (sum.is_empty(), sign) {
+            (true, "+") => "",
+            (true, _) => "-",
+            (false, "+") => " + ",
+            (false, _) => " - ",
+        });
+        if *position == 0 {
+            sum.push_str(&magnitude.to_string());
+        } else if magnitude == F::one() {
+            sum.push_str(&name(*position));
+        } else {
+            sum.push_str(&format!("{} * {}", magnitude, name(*position)));
+        }
+    }
+    sum
+}
+
+/// Writes the constraint `Σ a_i u_i * Σ b_i u_i = Σ c_i u_i` as `(a) * (b) = (c)`, where `u_i` is written as `name(i)`.
+fn constraint_to_string<F: Field>(a: &[(usize, F)], b: &[(usize, F)], c: &[(usize, F)], name: impl Fn(usize) -> String) -> String {
+    format!("({}) * ({}) = ({})", sum_to_string(a, &name), sum_to_string(b, &name), sum_to_string(c, &name))
+}
+
+/// Checks whether the name can be written as it is, rather than as a quoted string.
+fn is_identifier(name: &str) -> bool {
+    R1CSParser::parse(Rule::identifier, name).is_ok_and(|mut pairs| pairs.next().unwrap().as_str().len() == name.len())
+}
+
+/// Writes the name of a variable in a declaration, quoting it if it is not an identifier.
+fn quote(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Reads a quoted name, removing the quotes and the escaping backslashes.
+fn unquote(quoted: &str) -> String {
+    let mut name = String::new();
+    let mut characters = quoted[1..quoted.len() - 1].chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => name.extend(characters.next()),
+            _ => name.push(character),
+        }
+    }
+    name
+}
+
+/// Reads a variable written by its index, as in `u_3` or `u__3`, returning the index.
+fn parse_index(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix("u_")?.trim_start_matches('_');
+    if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Writes the constraint with its variables written as `u_i`.
+impl<F: Field> fmt::Display for R1CSConstraint<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", constraint_to_string(self.a().terms(), self.b().terms(), self.c().terms(), |i| format!("u_{}", i)))
+    }
+}
+
+/// Writes the system in the textual format described in the module `text_format`.
+impl<F: Field> fmt::Display for R1CS<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = self.index_prefix();
+        let names: Vec<String> = self.variable_names().into_iter().enumerate()
+            .map(|(i, name)| if is_identifier(&name) { name } else { format!("{}{}", prefix, i) })
+            .collect();
+        let name = |i: usize| names[i].clone();
+        writeln!(f, "variables {}", self.num_variables())?;
+        for (name, position) in self.inputs() {
+            writeln!(f, "input {} = {}{}", quote(name), prefix, position)?;
+        }
+        for (name, position) in self.named_variables() {
+            writeln!(f, "let {} = {}{}", quote(name), prefix, position)?;
+        }
+        for (i, hint) in self.hints() {
+            match hint {
+                Hint::Inverse(x) => writeln!(f, "hint {} = inverse({})", names[*i], sum_to_string(x.terms(), name))?,
+                Hint::Bit { source, index, size } => writeln!(f, "hint {} = bit({}, {}, {})", names[*i], names[*source], index, size)?,
+            }
+        }
+        for constraint in self.constraints() {
+            writeln!(f, "{}", constraint_to_string(constraint.a(), constraint.b(), constraint.c(), name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a system in the textual format described in the module `text_format`. An error is returned
+/// if the text does not follow the grammar in *r1cs.pest*, or if it uses undeclared variables.
+impl<F: Field> FromStr for R1CS<F> {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let system = match R1CSParser::parse(Rule::system, text) {
+            Ok(mut pairs) => pairs.next().unwrap(),
+            Err(e) => return Err(format!("Error parsing R1CS: {}", e)),
+        };
+        let mut reader = SystemReader { r1cs: R1CS::new(), names: HashMap::new(), num_variables: None, highest_index: 0 };
+        for pair in system.into_inner() {
+            reader.visit_statement(pair)?;
+        }
+        let num_variables = reader.num_variables.unwrap_or(reader.highest_index + 1);
+        if reader.highest_index >= num_variables {
+            return Err(format!("The variable u_{} is used, but the system has only {} variables", reader.highest_index, num_variables));
+        }
+        reader.r1cs.reserve_variables(num_variables);
+        Ok(reader.r1cs)
+    }
+}
+
+/// The state while reading a system in the textual format.
+struct SystemReader<F: Field> {
+    r1cs: R1CS<F>,
+    /// The indices of the declared variables, by their names.
+    names: HashMap<String, usize>,
+    /// The number of variables given in the first line, if any.
+    num_variables: Option<usize>,
+    /// The highest index of the variables used so far.
+    highest_index: usize,
+}
+
+impl<F: Field> SystemReader<F> {
+    /// Reads a line of the system, i.e. a declaration, a hint or a constraint.
+    fn visit_statement(&mut self, pair: pest::iterators::Pair<Rule>) -> Result<(), String> {
+        let line = pair.line_col().0;
+        let rule = pair.as_rule();
+        let mut inner = pair.into_inner();
+        match rule {
+            Rule::num_variables => {
+                let number = inner.next().unwrap().as_str();
+                let number = number.replace('_', "").parse().map_err(|_| format!("The number of variables {} is too large", number))?;
+                self.num_variables = Some(number);
+            }
+            Rule::input_declaration | Rule::let_declaration => {
+                let name = inner.next().unwrap();
+                let name = match name.as_rule() {
+                    Rule::quoted_name => unquote(name.as_str()),
+                    _ => name.as_str().to_string(),
+                };
+                let i = self.visit_index(inner.next().unwrap())?;
+                if i == 0 {
+                    return Err(format!("The variable `{}` at line {} cannot be declared as u_0, which is the constant 1", name, line));
+                }
+                if self.names.insert(name.clone(), i).is_some() {
+                    return Err(format!("The variable `{}` at line {} is declared twice", name, line));
+                }
+                if rule == Rule::input_declaration {
+                    if self.r1cs.inputs().any(|(_, position)| position == i) {
+                        return Err(format!("The input `{}` at line {} is the same variable as another input", name, line));
+                    }
+                    self.r1cs.add_input_variable_at(name, i);
+                } else {
+                    self.r1cs.name_variable(name, i);
+                }
+            }
+            Rule::inverse_hint => {
+                let i = self.visit_variable(inner.next().unwrap())?;
+                let sum = self.visit_sum(inner.next().unwrap())?;
+                self.r1cs.add_hint(i, Hint::Inverse(sum));
+            }
+            Rule::bit_hint => {
+                let i = self.visit_variable(inner.next().unwrap())?;
+                let source = self.visit_variable(inner.next().unwrap())?;
+                let mut numbers = inner.map(|number| number.as_str().replace('_', "").parse::<usize>());
+                let (Ok(index), Ok(size)) = (numbers.next().unwrap(), numbers.next().unwrap()) else {
+                    return Err(format!("The bit of the hint at line {} is too large", line));
+                };
+                if index >= size {
+                    return Err(format!("The hint at line {} takes bit {} of a {}-bit value", line, index, size));
+                }
+                self.r1cs.add_hint(i, Hint::Bit { source, index, size });
+            }
+            Rule::constraint => {
+                let a = self.visit_sum(inner.next().unwrap())?;
+                let b = self.visit_sum(inner.next().unwrap())?;
+                let c = self.visit_sum(inner.next().unwrap())?;
+                self.r1cs.add_constraint(R1CSConstraint::new(a, b, c));
+            }
+            Rule::EOI => {}
+            _ => unreachable!("Unexpected rule {:?}", rule),
+        }
+        Ok(())
+    }
+
+    /// Reads a sum `Σ x_i u_i`.
+    fn visit_sum(&mut self, pair: pest::iterators::Pair<Rule>) -> Result<LinearCombination<F>, String> {
+        let mut sum = LinearCombination::new();
+        let mut is_negative = false;
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::plus => is_negative = false,
+                Rule::minus => is_negative = true,
+                _ => {
+                    let mut coefficient = F::one();
+                    let mut i = 0;
+                    for factor in pair.into_inner() {
+                        match factor.as_rule() {
+                            Rule::number => coefficient = parse_constant(factor.as_str()),
+                            _ => i = self.visit_variable(factor)?,
+                        }
+                    }
+                    sum.add_term(i, if is_negative { -coefficient } else { coefficient });
+                }
+            }
+        }
+        Ok(sum)
+    }
+
+    /// Reads a variable, written by its name or its index, and returns its index.
+    fn visit_variable(&mut self, pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        let i = match self.names.get(pair.as_str()) {
+            Some(i) => *i,
+            None => parse_index(pair.as_str()).ok_or(format!("Unknown variable `{}` at line {}", pair.as_str(), pair.line_col().0))?,
+        };
+        self.highest_index = self.highest_index.max(i);
+        Ok(i)
+    }
+
+    /// Reads a variable written by its index, as in the declarations, and returns the index.
+    fn visit_index(&mut self, pair: pest::iterators::Pair<Rule>) -> Result<usize, String> {
+        let i = parse_index(pair.as_str()).ok_or(format!("The index of `{}` at line {} is too large", pair.as_str(), pair.line_col().0))?;
+        self.highest_index = self.highest_index.max(i);
+        Ok(i)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::builder::ConstraintSystem;
+    use crate::field::Bn254;
+    use crate::parser::equation2r1cs;
+
+    /// Writes the system, reads it back, and checks that it is written identically.
+    fn round_trip(r1cs: &R1CS) -> R1CS {
+        let text = r1cs.to_string();
+        let read: R1CS = text.parse().unwrap_or_else(|e| panic!("{}\nin\n{}", e, text));
+        assert_eq!(read.to_string(), text);
+        assert_eq!(read.num_variables(), r1cs.num_variables());
+        assert!(read.constraints().eq(r1cs.constraints()));
+        read
+    }
+
+    #[test]
+    fn test_written_system() {
+        let r1cs = equation2r1cs("let t = x^2; t - 2x != 3".to_string()).unwrap();
+        let expected = "variables 6\ninput x = u_1\nlet t = u_2\nhint u_5 = inverse(-3 + u_4)\n(x) * (x) = (t)\n(2) * (x) = (u_3)\n(1) * (t - u_3) = (u_4)\n(-3 + u_4) * (u_5) = (1)\n";
+        assert_eq!(r1cs.to_string(), expected);
+        let constraint = R1CSConstraint::<Bn254>::new([(0, Bn254::from(-1i64)), (2, Bn254::from(5i64))].into(), LinearCombination::new(), LinearCombination::variable(3));
+        assert_eq!(constraint.to_string(), "(-1 + 5 * u_2) * (0) = (u_3)");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let programs = [
+            "x^3 + x + 5 = 35",
+            "let t = z*y + x; fn f(a, b) = a*b - 3; sum(i in 0..4, w[i] * f(t, i)) != 7",
+            "bits(x, 8) >= 18",
+            "let u_1 = x * y; u_1 + u_2 = 5",
+        ];
+        for program in programs {
+            let r1cs = equation2r1cs(program.to_string()).unwrap();
+            let read = round_trip(&r1cs);
+            let inputs: HashMap<String, Bn254> = r1cs.inputs().map(|(name, i)| (name.to_string(), Bn254::from(i as u64 + 20))).collect();
+            assert_eq!(read.generate_witness(&inputs), r1cs.generate_witness(&inputs), "Different witnesses for {}", program);
+        }
+        let cs = ConstraintSystem::<Bn254>::new();
+        let x = cs.input("the \"first\" input");
+        let y = cs.input("w\\2");
+        cs.assert_equal(x * y, Bn254::from(6i64));
+        let r1cs = cs.into_r1cs();
+        let read = round_trip(&r1cs);
+        assert!(read.to_string().contains("input \"the \\\"first\\\" input\" = u_1"));
+        assert_eq!(read.inputs().collect::<Vec<_>>(), r1cs.inputs().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_hand_written_system() {
+        let text = "
+            // x is a root of (x - 1)(x - 2)
+            input x = u_1
+            (x - 1) * (x - 0b10) = (u_2)
+            (1) * (2u_2) = (0)
+        ";
+        let r1cs: R1CS = text.parse().unwrap();
+        assert_eq!(r1cs.num_variables(), 3);
+        assert_eq!(r1cs.size(), 2);
+        for (x, is_root) in [(1, true), (2, true), (3, false)] {
+            let inputs = HashMap::from([("x".to_string(), Bn254::from(x as u64))]);
+            assert_eq!(r1cs.generate_witness(&inputs).is_ok(), is_root, "Unexpected result for x = {}", x);
+        }
+        let r1cs: R1CS = "variables 10\n(u_1) * (u_1) = (u_2)".parse().unwrap();
+        assert_eq!(r1cs.num_variables(), 10);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("input x = u_1\n(x) * (y) = (u_2)", "Unknown variable `y` at line 2"),
+            ("variables 2\n(u_1) * (u_1) = (u_2)", "only 2 variables"),
+            ("input x = u_1\ninput x = u_2", "declared twice"),
+            ("input x = u_1\ninput y = u_1", "same variable as another input"),
+            ("input x = u_0", "cannot be declared as u_0"),
+            ("hint u_2 = bit(u_1, 4, 4)", "bit 4 of a 4-bit value"),
+            ("(u_1) * (u_1) = u_2", "Error parsing R1CS"),
+            ("(x) * (x) = (x)\ninput x = u_1", "Error parsing R1CS"),
+        ];
+        for (text, expected) in cases {
+            let error = text.parse::<R1CS>().err().unwrap();
+            assert!(error.contains(expected), "Unexpected error for {:?}: {}", text, error);
+        }
+    }
+}
@@ -0,0 +1,551 @@
+//! This module implements witness generation for R1CS systems.
+//!
+//! A witness for a R1CS system is an assignment of values to all the variables `u_0, u_1, ...`
+//! (where `u_0 = 1`) which satisfies every constraint. The user only provides the values of the
+//! named input variables, and the values of the internal variables are computed from these.
+//!
+//! Most internal variables are defined by a constraint where the variable is the only unknown,
+//! and where it appears only in the right hand side. For example, given `u_i` and `u_j`, the
+//! constraint `u_i * u_j = u_k` defines `u_k`. We call such a constraint *solvable*. The remaining
+//! internal variables (e.g. inverses) are computed from *hints* stored in the system.
+//!
+//! The constraints are not necessarily ordered such that they can be solved one by one from
+//! the beginning. Instead, we first compute a *schedule*: an order in which the variables can be
+//! computed, found by repeatedly picking a solvable constraint or a hint whose dependencies are
+//! all known.
+//!
+//! The schedule is then split into *levels*: the inputs are at level 0, and a variable is at one
+//! level above the highest level of the variables it is computed from. The variables at the same
+//! level do not depend on each other, so they can be computed at the same time, which is done in
+//! parallel with the feature `parallel`.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::field::{Bn254, Field};
+use crate::linear_combination::LinearCombination;
+use crate::parallel;
+use crate::r1cs::R1CS;
+
+/// A hint telling witness generation how to compute a variable which is not defined by a
+/// solvable constraint.
+pub enum Hint<F: Field> {
+    /// The variable is the inverse of the linear combination `Σ x_i u_i`.
+    Inverse(LinearCombination<F>),
+    /// The variable is bit number `index` of the variable `u_source`, which is
+    /// expected to fit in `size` bits.
+    Bit { source: usize, index: usize, size: usize },
+}
+
+impl<F: Field> Hint<F> {
+    /// Returns the variables that must be known before the hint can be evaluated.
+    fn dependencies(&self) -> Vec<usize> {
+        match self {
+            Hint::Inverse(x) => x.variables().collect(),
+            Hint::Bit { source, .. } => vec![*source],
+        }
+    }
+
+    /// Evaluates the hint for the variable `u_i`, where `value(j)` is the value of the dependency `u_j`.
+    fn evaluate(&self, i: usize, value: impl Fn(usize) -> F) -> Result<F, String> {
+        match self {
+            Hint::Inverse(x) => {
+                let sum = x.iter().fold(F::zero(), |sum, (j, x_j)| sum + x_j * value(j));
+                sum.inverse().ok_or(format!("Cannot compute u_{} as the inverse of zero. Is a `!=` constraint violated?", i))
+            }
+            Hint::Bit { source, index, size } => {
+                let value = value(*source);
+                if value.bit_length() > *size {
+                    return Err(format!("Cannot compute the bits of u_{}, since its value {} does not fit in {} bits", source, value, size));
+                }
+                Ok(if value.bit(*index) { F::one() } else { F::zero() })
+            }
+        }
+    }
+}
+
+/// A step in the witness schedule, computing a single variable.
+pub(crate) enum Step<F: Field> {
+    /// Compute the variable `variable` by solving the constraint with index `constraint`,
+    /// where `inverse` is the inverse of the coefficient of the variable in the right hand side.
+    Constraint { constraint: usize, variable: usize, inverse: F },
+    /// Compute a variable by evaluating the hint with index `hint`.
+    Hint { hint: usize },
+}
+
+/// Something that uses the value of a variable: a constraint or a hint (given by their indices).
+#[derive(Clone, Copy)]
+enum Dependent {
+    Constraint(usize),
+    Hint(usize),
+}
+
+/// Computes an order in which the variables of the system can be computed, given that the
+/// variables in `known` are already known. On return, `known` tells which variables the
+/// schedule computes (in addition to the initially known ones).
+fn compute_schedule<F: Field>(r1cs: &R1CS<F>, known: &mut [bool]) -> Vec<Step<F>> {
+    let constraint_variables: Vec<BTreeMap<usize, bool>> = r1cs.constraints().map(|constraint| constraint.variables()).collect();
+    let hint_dependencies: Vec<Vec<usize>> = r1cs.hints().iter().map(|(_, hint)| hint.dependencies()).collect();
+
+    // For each variable, the constraints and hints in which it appears, and for each
+    // constraint and hint, the number of its variables which are still unknown.
+    let mut dependents = vec![Vec::new(); known.len()];
+    let mut unknown_constraint_variables = vec![0; constraint_variables.len()];
+    let mut unknown_hint_variables = vec![0; hint_dependencies.len()];
+    let mut queue = VecDeque::new();
+    for (index, variables) in constraint_variables.iter().enumerate() {
+        for variable in variables.keys() {
+            dependents[*variable].push(Dependent::Constraint(index));
+            if !known[*variable] {
+                unknown_constraint_variables[index] += 1;
+            }
+        }
+        if unknown_constraint_variables[index] == 1 {
+            queue.push_back(Dependent::Constraint(index));
+        }
+    }
+    for (index, dependencies) in hint_dependencies.iter().enumerate() {
+        for variable in dependencies {
+            dependents[*variable].push(Dependent::Hint(index));
+            if !known[*variable] {
+                unknown_hint_variables[index] += 1;
+            }
+        }
+        if unknown_hint_variables[index] == 0 {
+            queue.push_back(Dependent::Hint(index));
+        }
+    }
+
+    let mut steps = Vec::new();
+    while let Some(dependent) = queue.pop_front() {
+        let (step, variable) = match dependent {
+            Dependent::Constraint(index) => {
+                if unknown_constraint_variables[index] != 1 {
+                    continue;
+                }
+                let constraint = r1cs.constraint(index);
+                let (variable, only_rhs) = constraint_variables[index].iter()
+                    .find(|(variable, _)| !known[**variable])
+                    .map(|(variable, only_rhs)| (*variable, *only_rhs))
+                    .expect("Constraint with one unknown variable has no unknown variable.");
+                let inverse = match constraint.rhs_coefficient(variable).inverse() {
+                    Some(inverse) if only_rhs => inverse,
+                    _ => continue,
+                };
+                (Step::Constraint { constraint: index, variable, inverse }, variable)
+            }
+            Dependent::Hint(index) => {
+                let variable = r1cs.hints()[index].0;
+                if known[variable] {
+                    continue;
+                }
+                (Step::Hint { hint: index }, variable)
+            }
+        };
+        steps.push(step);
+        known[variable] = true;
+        for dependent in dependents[variable].iter() {
+            match *dependent {
+                Dependent::Constraint(index) => {
+                    unknown_constraint_variables[index] -= 1;
+                    if unknown_constraint_variables[index] == 1 {
+                        queue.push_back(*dependent);
+                    }
+                }
+                Dependent::Hint(index) => {
+                    unknown_hint_variables[index] -= 1;
+                    if unknown_hint_variables[index] == 0 {
+                        queue.push_back(*dependent);
+                    }
+                }
+            }
+        }
+    }
+    steps
+}
+
+/// Splits the steps of a schedule into levels, such that every step only depends on variables
+/// computed at lower levels, or on the inputs. The steps keep their order within each level.
+fn split_into_levels<F: Field>(r1cs: &R1CS<F>, steps: Vec<Step<F>>) -> Vec<Vec<Step<F>>> {
+    let mut variable_levels = vec![0; r1cs.num_variables()];
+    let mut levels: Vec<Vec<Step<F>>> = Vec::new();
+    for step in steps {
+        let (variable, dependencies) = match step {
+            Step::Constraint { constraint, variable, .. } => {
+                (variable, r1cs.constraint(constraint).variables().into_keys().filter(|i| *i != variable).collect())
+            }
+            Step::Hint { hint } => {
+                let (variable, hint) = &r1cs.hints()[hint];
+                (*variable, hint.dependencies())
+            }
+        };
+        let level = dependencies.iter().map(|i| variable_levels[*i]).max().unwrap_or(0) + 1;
+        variable_levels[variable] = level;
+        if levels.len() < level {
+            levels.resize_with(level, Vec::new);
+        }
+        levels[level - 1].push(step);
+    }
+    levels
+}
+
+/// A precomputed order in which the variables of a system are computed from its inputs.
+///
+/// Computing the schedule walks all the constraints of the system, which takes far longer than
+/// evaluating them. When witnesses are needed for many input assignments (e.g. test vectors or
+/// fuzzing), the schedule is therefore computed once by `R1CS::witness_schedule`, and reused for
+/// each assignment, either one at a time with `generate_witness`, or for a whole batch at once
+/// with `generate_witnesses`.
+pub struct WitnessSchedule<'a, F: Field = Bn254> {
+    r1cs: &'a R1CS<F>,
+    /// The steps of the schedule, split into levels (see `split_into_levels`).
+    levels: Vec<Vec<Step<F>>>,
+}
+
+impl<'a, F: Field> WitnessSchedule<'a, F> {
+    /// Computes the schedule of a system. An error is returned if some variable cannot be computed from the inputs.
+    fn new(r1cs: &'a R1CS<F>) -> Result<Self, String> {
+        let mut known = vec![false; r1cs.num_variables()];
+        known[0] = true;
+        for (_, position) in r1cs.inputs() {
+            known[position] = true;
+        }
+        let steps = compute_schedule(r1cs, &mut known);
+        if let Some(position) = known.iter().position(|is_known| !is_known) {
+            return Err(format!("Variable u_{} cannot be computed from the inputs", position));
+        }
+        Ok(WitnessSchedule { r1cs, levels: split_into_levels(r1cs, steps) })
+    }
+
+    /// Returns the steps of the schedule, in an order in which they can be evaluated one by one.
+    pub(crate) fn steps(&self) -> impl Iterator<Item = &Step<F>> + '_ {
+        self.levels.iter().flatten()
+    }
+
+    /// Generates a witness for the given values of the named input variables, just as `R1CS::generate_witness`.
+    pub fn generate_witness(&self, inputs: &HashMap<String, F>) -> Result<Vec<F>, String> {
+        self.complete_witness(self.r1cs.input_values(inputs)?)
+    }
+
+    /// Computes the internal variables of a witness, given a vector where only the inputs are set.
+    fn complete_witness(&self, mut values: Vec<F>) -> Result<Vec<F>, String> {
+        for level in self.levels.iter() {
+            let level_values = parallel::map(0..level.len(), |s| self.evaluate_step(&level[s], &values));
+            for value in level_values {
+                let (variable, value) = value?;
+                values[variable] = value;
+            }
+        }
+        match self.r1cs.first_unsatisfied_constraint(&values) {
+            Some(index) => Err(format!("Constraint {} is not satisfied by the inputs", index)),
+            None => Ok(values),
+        }
+    }
+
+    /// Evaluates a step of the schedule, given the values of the variables it depends on.
+    /// Returns the variable computed by the step, together with its value.
+    fn evaluate_step(&self, step: &Step<F>, values: &[F]) -> Result<(usize, F), String> {
+        match *step {
+            Step::Constraint { constraint, variable, inverse } => {
+                let (a, b, c) = self.r1cs.constraint(constraint).evaluate(values);
+                Ok((variable, (a * b - c) * inverse))
+            }
+            Step::Hint { hint } => {
+                let (variable, hint) = &self.r1cs.hints()[hint];
+                Ok((*variable, hint.evaluate(*variable, |j| values[j])?))
+            }
+        }
+    }
+
+    /// Generates the witnesses for a batch of input assignments, each giving the values of the named
+    /// input variables. The witnesses are stored column by column (see `WitnessBatch`), and each
+    /// step of the schedule is evaluated for all the assignments at once.
+    ///
+    /// An assignment for which no witness can be generated (e.g. since an input is missing, or the
+    /// constraints are not satisfied) does not stop the others: its error is kept in the batch.
+    pub fn generate_witnesses(&self, assignments: &[HashMap<String, F>]) -> WitnessBatch<F> {
+        let n = assignments.len();
+        let mut values = vec![F::zero(); self.r1cs.num_variables() * n];
+        let mut errors: Vec<Option<String>> = vec![None; n];
+        values[..n].fill(F::one());
+        for (k, inputs) in assignments.iter().enumerate() {
+            match self.r1cs.check_inputs(inputs) {
+                Ok(()) => {
+                    for (name, position) in self.r1cs.variables().iter() {
+                        values[position * n + k] = inputs[name];
+                    }
+                }
+                Err(error) => errors[k] = Some(error),
+            }
+        }
+
+        for level in self.levels.iter() {
+            let columns = parallel::map(0..level.len(), |s| self.evaluate_step_batch(&level[s], &values, n));
+            for (variable, column, column_errors) in columns {
+                values[variable * n..(variable + 1) * n].copy_from_slice(&column);
+                for (k, error) in column_errors {
+                    errors[k].get_or_insert(error);
+                }
+            }
+        }
+
+        let unsatisfied = parallel::map(0..self.r1cs.size(), |r| {
+            let (a, b, c) = self.r1cs.constraint(r).evaluate_batch(&values, n);
+            (0..n).filter(|k| a[*k] * b[*k] != c[*k]).collect::<Vec<usize>>()
+        });
+        for (r, assignments) in unsatisfied.into_iter().enumerate() {
+            for k in assignments {
+                errors[k].get_or_insert_with(|| format!("Constraint {} is not satisfied by the inputs", r));
+            }
+        }
+        WitnessBatch { size: n, values, errors }
+    }
+
+    /// Evaluates a step of the schedule for a batch of `n` assignments, stored column by column.
+    /// Returns the variable computed by the step, its values in the assignments, and the errors
+    /// of the assignments for which it cannot be computed (by the index of the assignment).
+    fn evaluate_step_batch(&self, step: &Step<F>, values: &[F], n: usize) -> (usize, Vec<F>, Vec<(usize, String)>) {
+        match *step {
+            Step::Constraint { constraint, variable, inverse } => {
+                let (a, b, c) = self.r1cs.constraint(constraint).evaluate_batch(values, n);
+                let column = (0..n).map(|k| (a[k] * b[k] - c[k]) * inverse).collect();
+                (variable, column, Vec::new())
+            }
+            Step::Hint { hint } => {
+                let (variable, hint) = &self.r1cs.hints()[hint];
+                let mut errors = Vec::new();
+                let column = (0..n).map(|k| {
+                    hint.evaluate(*variable, |j| values[j * n + k]).unwrap_or_else(|error| {
+                        errors.push((k, error));
+                        F::zero()
+                    })
+                }).collect();
+                (*variable, column, errors)
+            }
+        }
+    }
+}
+
+/// The witnesses generated for a batch of input assignments (see `WitnessSchedule::generate_witnesses`).
+///
+/// The witnesses are stored in a column-major layout: viewing the batch as a matrix with one row
+/// per assignment and one column per variable, the values of each variable in all the assignments
+/// are stored next to each other. Hence, `column(i)` gives the values of `u_i` without copying,
+/// while `witness(k)` gathers the witness of assignment number `k`.
+pub struct WitnessBatch<F: Field = Bn254> {
+    /// The number of assignments in the batch.
+    size: usize,
+    /// The values of the variables, where the value of `u_i` in assignment `k` is `values[i * size + k]`.
+    values: Vec<F>,
+    /// The errors of the assignments for which no witness could be generated.
+    errors: Vec<Option<String>>,
+}
+
+impl<F: Field> WitnessBatch<F> {
+    /// Returns the number of assignments in the batch.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Checks whether the batch has no assignments.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the values of the variable `u_i` in all the assignments, in the order of the assignments.
+    /// The values for assignments with an error are unspecified.
+    pub fn column(&self, i: usize) -> &[F] {
+        &self.values[i * self.size..(i + 1) * self.size]
+    }
+
+    /// Returns the witness of assignment number `k`, as returned by `R1CS::generate_witness`,
+    /// or the error explaining why no witness could be generated for it.
+    pub fn witness(&self, k: usize) -> Result<Vec<F>, String> {
+        match &self.errors[k] {
+            Some(error) => Err(error.clone()),
+            None => Ok(self.values.iter().skip(k).step_by(self.size).copied().collect()),
+        }
+    }
+
+    /// Returns the error of assignment number `k`, or `None` if a witness was generated for it.
+    pub fn error(&self, k: usize) -> Option<&str> {
+        self.errors[k].as_deref()
+    }
+}
+
+impl<F: Field> R1CS<F> {
+    /// Generates a witness for the system, given the values of the named input variables.
+    ///
+    /// The witness is returned as a vector `w` where `w[i]` is the value of `u_i` (so that
+    /// `w[0] = 1`). An error is returned if an input is missing or unknown, if some variable
+    /// cannot be computed, or if the inputs do not satisfy the constraints.
+    ///
+    /// This computes a new schedule for every witness. To generate many witnesses for the
+    /// same system, use `witness_schedule` or `generate_witnesses` instead.
+    pub fn generate_witness(&self, inputs: &HashMap<String, F>) -> Result<Vec<F>, String> {
+        let values = self.input_values(inputs)?;
+        self.witness_schedule()?.complete_witness(values)
+    }
+
+    /// Computes the order in which the variables of the system are computed from its inputs,
+    /// which can be reused to generate any number of witnesses. An error is returned if some
+    /// variable cannot be computed from the inputs.
+    pub fn witness_schedule(&self) -> Result<WitnessSchedule<'_, F>, String> {
+        WitnessSchedule::new(self)
+    }
+
+    /// Generates the witnesses for a batch of input assignments, in a column-major layout (see
+    /// `WitnessSchedule::generate_witnesses`). An error is returned if some variable cannot be
+    /// computed from the inputs, while the errors of single assignments are kept in the batch.
+    pub fn generate_witnesses(&self, assignments: &[HashMap<String, F>]) -> Result<WitnessBatch<F>, String> {
+        Ok(self.witness_schedule()?.generate_witnesses(assignments))
+    }
+
+    /// Checks that the given values are exactly the values of the named input variables.
+    fn check_inputs(&self, inputs: &HashMap<String, F>) -> Result<(), String> {
+        for name in inputs.keys() {
+            if !self.variables().contains_key(name) {
+                return Err(format!("Unknown input variable {}", name));
+            }
+        }
+        match self.variables().keys().find(|name| !inputs.contains_key(*name)) {
+            Some(name) => Err(format!("Missing value for input variable {}", name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a vector of the values of all variables, where only `u_0 = 1` and the inputs are set.
+    fn input_values(&self, inputs: &HashMap<String, F>) -> Result<Vec<F>, String> {
+        self.check_inputs(inputs)?;
+        let mut values = vec![F::zero(); self.num_variables()];
+        values[0] = F::one();
+        for (name, position) in self.variables().iter() {
+            values[*position] = inputs[name];
+        }
+        Ok(values)
+    }
+
+    /// Checks whether the given witness satisfies all constraints of the system.
+    /// With the feature `parallel`, the constraints are checked in parallel.
+    pub fn is_satisfied(&self, witness: &[F]) -> bool {
+        witness.len() == self.num_variables()
+            && witness[0] == F::one()
+            && self.first_unsatisfied_constraint(witness).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Bn254;
+    use crate::parser::{equation2r1cs, poly2r1cs};
+
+    /// Creates the inputs of a witness from pairs of variable names and values.
+    fn inputs(values: &[(&str, i64)]) -> HashMap<String, Bn254> {
+        values.iter().map(|(name, value)| (name.to_string(), Bn254::from(*value))).collect()
+    }
+
+    #[test]
+    fn test_polynomial_witness() {
+        let r1cs = poly2r1cs("(x + y)^7 - 3x*y + 2".to_string(), 2183).unwrap();
+        let witness = r1cs.generate_witness(&inputs(&[("x", 1), ("y", 2)])).unwrap();
+        assert_eq!(witness.len(), r1cs.num_variables());
+        assert_eq!(witness[r1cs.get_variable_index("x").unwrap()], Bn254::from(1i64));
+        assert!(r1cs.is_satisfied(&witness));
+    }
+
+    #[test]
+    fn test_unsatisfied_polynomial() {
+        let r1cs = poly2r1cs("x^3 + x".to_string(), 10).unwrap();
+        assert!(r1cs.generate_witness(&inputs(&[("x", 2)])).is_ok());
+        assert!(r1cs.generate_witness(&inputs(&[("x", 3)])).is_err());
+    }
+
+    #[test]
+    fn test_missing_and_unknown_inputs() {
+        let r1cs = poly2r1cs("x*y".to_string(), 6).unwrap();
+        assert!(r1cs.generate_witness(&inputs(&[("x", 2)])).is_err());
+        assert!(r1cs.generate_witness(&inputs(&[("x", 2), ("y", 3), ("z", 1)])).is_err());
+    }
+
+    #[test]
+    fn test_inequality_witness() {
+        let r1cs = equation2r1cs("x^2 - y != 4".to_string()).unwrap();
+        let witness = r1cs.generate_witness(&inputs(&[("x", 3), ("y", 1)])).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+        let error = r1cs.generate_witness(&inputs(&[("x", 3), ("y", 5)])).unwrap_err();
+        assert!(error.contains("inverse of zero"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn test_modified_witness_is_not_satisfied() {
+        let r1cs = poly2r1cs("x*y + x".to_string(), 8).unwrap();
+        let mut witness = r1cs.generate_witness(&inputs(&[("x", 2), ("y", 3)])).unwrap();
+        let last = witness.len() - 1;
+        witness[last] = witness[last] + Bn254::one();
+        assert!(!r1cs.is_satisfied(&witness));
+    }
+
+    #[test]
+    fn test_schedule_levels() {
+        // The products x*y and z*w do not depend on each other, while their sum depends on both.
+        let r1cs = poly2r1cs("x*y + z*w".to_string(), 10).unwrap();
+        let mut known = vec![false; r1cs.num_variables()];
+        known[0] = true;
+        for (_, i) in r1cs.inputs() {
+            known[i] = true;
+        }
+        let levels = split_into_levels(&r1cs, compute_schedule(&r1cs, &mut known));
+        assert_eq!(levels.iter().map(|level| level.len()).collect::<Vec<_>>(), vec![2, 1]);
+        let witness = r1cs.generate_witness(&inputs(&[("x", 2), ("y", 3), ("z", 1), ("w", 4)])).unwrap();
+        assert!(r1cs.is_satisfied(&witness));
+    }
+
+    #[test]
+    fn test_matrix_products() {
+        let r1cs = poly2r1cs("(x + 1)^3 - 7x*y + 2".to_string(), 15).unwrap();
+        let witness = r1cs.generate_witness(&inputs(&[("x", 2), ("y", 1)])).unwrap();
+        let (a, b, c) = r1cs.matrix_products(&witness);
+        assert_eq!(a.len(), r1cs.size());
+        assert!(a.iter().zip(b.iter()).zip(c.iter()).all(|((a, b), c)| *a * *b == *c));
+        let mut wrong_witness = witness.clone();
+        wrong_witness[r1cs.get_variable_index("y").unwrap()] = Bn254::from(2i64);
+        assert_eq!(r1cs.first_unsatisfied_constraint(&witness), None);
+        assert!(r1cs.first_unsatisfied_constraint(&wrong_witness).is_some());
+    }
+
+    #[test]
+    fn test_batch_matches_single_witnesses() {
+        let r1cs = equation2r1cs("let t = x*y + 1; t^3 - x != 5; bits(y, 4) < 9".to_string()).unwrap();
+        let assignments: Vec<HashMap<String, Bn254>> = (0..6)
+            .map(|k| inputs(&[("x", k), ("y", 2 * k + 1)]))
+            .collect();
+        let schedule = r1cs.witness_schedule().unwrap();
+        let batch = schedule.generate_witnesses(&assignments);
+        assert_eq!(batch.len(), assignments.len());
+        for (k, assignment) in assignments.iter().enumerate() {
+            assert_eq!(batch.witness(k), r1cs.generate_witness(assignment));
+            assert_eq!(batch.witness(k), schedule.generate_witness(assignment));
+        }
+        // y = 2k + 1 < 9 holds exactly for k < 4.
+        assert_eq!((0..6).filter(|k| batch.error(*k).is_none()).count(), 4);
+        let y = r1cs.get_variable_index("y").unwrap();
+        assert_eq!(batch.column(y), (0..6i64).map(|k| Bn254::from(2 * k + 1)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_batch_errors() {
+        let r1cs = equation2r1cs("x^2 - y != 4".to_string()).unwrap();
+        let assignments = vec![
+            inputs(&[("x", 3), ("y", 1)]),
+            inputs(&[("x", 3), ("y", 5)]),
+            inputs(&[("x", 3)]),
+            inputs(&[("x", 1), ("y", 1), ("z", 1)]),
+        ];
+        let batch = r1cs.generate_witnesses(&assignments).unwrap();
+        assert!(r1cs.is_satisfied(&batch.witness(0).unwrap()));
+        assert!(batch.error(1).unwrap().contains("inverse of zero"));
+        assert_eq!(batch.error(2), Some("Missing value for input variable y"));
+        assert_eq!(batch.error(3), Some("Unknown input variable z"));
+        assert!(r1cs.generate_witnesses(&[]).unwrap().is_empty());
+    }
+}